@@ -0,0 +1,586 @@
+//! Psychometric item analysis
+//!
+//! Per-question difficulty and discrimination, and per-answer selection rates, from historical attempts.
+//! Only attempts a question was generated for are counted towards it. Unanswered questions are scored as incorrect.
+//!
+//! Reliability of the scores is estimated per generation, as attempts of a generation share the same questions.
+use std::collections::BTreeMap;
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attempt::Attempt,
+    misc::{ScoreReport, calculate_score, compare_answers},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExamItemAnalysis {
+    #[serde(rename = "examId")]
+    pub exam_id: ObjectId,
+    /// Number of attempts the analysis was built from
+    #[serde(rename = "numAttempts")]
+    pub num_attempts: usize,
+    pub questions: Vec<QuestionAnalysis>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionAnalysis {
+    #[serde(rename = "questionId")]
+    pub question_id: ObjectId,
+    #[serde(rename = "questionSetId")]
+    pub question_set_id: ObjectId,
+    /// Number of attempts the question was generated for
+    #[serde(rename = "exposureCount")]
+    pub exposure_count: usize,
+    /// Number of attempts the question was submitted in
+    #[serde(rename = "answeredCount")]
+    pub answered_count: usize,
+    /// Difficulty index (p-value): fraction of exposed attempts answering correctly. Higher is easier.
+    ///
+    /// `None` if the question was never exposed.
+    pub difficulty: Option<f64>,
+    /// Correlation between answering the question correctly and the fraction correct of the rest of the attempt.
+    ///
+    /// `None` if either has no variance across exposed attempts.
+    #[serde(rename = "pointBiserial")]
+    pub point_biserial: Option<f64>,
+    pub answers: Vec<AnswerAnalysis>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnswerAnalysis {
+    #[serde(rename = "answerId")]
+    pub answer_id: ObjectId,
+    #[serde(rename = "isCorrect")]
+    pub is_correct: bool,
+    /// Number of attempts the answer was generated for
+    #[serde(rename = "exposureCount")]
+    pub exposure_count: usize,
+    /// Number of attempts selecting the answer
+    #[serde(rename = "selectionCount")]
+    pub selection_count: usize,
+    /// `selection_count` as a fraction of `exposure_count`. For incorrect answers, this is the distractor rate.
+    ///
+    /// `None` if the answer was never exposed.
+    #[serde(rename = "selectionRate")]
+    pub selection_rate: Option<f64>,
+}
+
+#[derive(Default)]
+struct QuestionTally {
+    question_set_id: ObjectId,
+    answered_count: usize,
+    /// Item score and rest score of each exposed attempt
+    scores: Vec<(f64, Option<f64>)>,
+    /// Answer id, correctness, exposure count and selection count
+    answers: Vec<(ObjectId, bool, usize, usize)>,
+}
+
+/// Analyses each question and answer of an exam over its attempts.
+///
+/// Attempts of other exams are ignored. Questions are ordered by id, answers in exam order.
+pub fn analyse_items(exam_id: ObjectId, attempts: &[Attempt]) -> ExamItemAnalysis {
+    let mut tallies: BTreeMap<ObjectId, QuestionTally> = BTreeMap::new();
+    let mut num_attempts = 0;
+
+    for attempt in attempts.iter().filter(|a| a.exam_id == exam_id) {
+        num_attempts += 1;
+
+        let results: Vec<(ObjectId, bool)> = attempt
+            .question_sets
+            .iter()
+            .flat_map(|qs| qs.questions.iter())
+            .filter(|q| !q.generated.is_empty())
+            .map(|q| (q.id, compare_answers(&q.answers, &q.generated, &q.selected)))
+            .collect();
+        let num_correct = results.iter().filter(|(_, c)| *c).count();
+
+        for question_set in attempt.question_sets.iter() {
+            for question in question_set.questions.iter() {
+                let tally = tallies.entry(question.id).or_insert_with(|| QuestionTally {
+                    question_set_id: question_set.id,
+                    answers: question
+                        .answers
+                        .iter()
+                        .map(|a| (a.id, a.is_correct, 0, 0))
+                        .collect(),
+                    ..Default::default()
+                });
+
+                if question.generated.is_empty() {
+                    continue;
+                }
+                let is_correct = results
+                    .iter()
+                    .any(|(id, correct)| *id == question.id && *correct);
+                let rest_score = (results.len() > 1).then(|| {
+                    (num_correct - usize::from(is_correct)) as f64 / (results.len() - 1) as f64
+                });
+                tally
+                    .scores
+                    .push((if is_correct { 1.0 } else { 0.0 }, rest_score));
+                if question.submission_time.is_some() {
+                    tally.answered_count += 1;
+                }
+
+                for (answer_id, _, exposure_count, selection_count) in tally.answers.iter_mut() {
+                    if question.generated.contains(answer_id) {
+                        *exposure_count += 1;
+                        if question.selected.contains(answer_id) {
+                            *selection_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let questions = tallies
+        .into_iter()
+        .map(|(question_id, tally)| {
+            let exposure_count = tally.scores.len();
+            let difficulty = (exposure_count > 0).then(|| {
+                tally.scores.iter().map(|(item, _)| item).sum::<f64>() / exposure_count as f64
+            });
+            let (items, rests): (Vec<f64>, Vec<f64>) = tally
+                .scores
+                .iter()
+                .filter_map(|(item, rest)| Some((*item, (*rest)?)))
+                .unzip();
+            let point_biserial = get_correlation(&items, &rests);
+
+            let answers = tally
+                .answers
+                .into_iter()
+                .map(
+                    |(answer_id, is_correct, exposure_count, selection_count)| AnswerAnalysis {
+                        answer_id,
+                        is_correct,
+                        exposure_count,
+                        selection_count,
+                        selection_rate: (exposure_count > 0)
+                            .then(|| selection_count as f64 / exposure_count as f64),
+                    },
+                )
+                .collect();
+
+            QuestionAnalysis {
+                question_id,
+                question_set_id: tally.question_set_id,
+                exposure_count,
+                answered_count: tally.answered_count,
+                difficulty,
+                point_biserial,
+                answers,
+            }
+        })
+        .collect();
+
+    ExamItemAnalysis {
+        exam_id,
+        num_attempts,
+        questions,
+    }
+}
+
+/// Pearson correlation, or `None` if either series has no variance
+fn get_correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len() as f64;
+    if xs.len() < 2 {
+        return None;
+    }
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+    for (x, y) in xs.iter().zip(ys) {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+    if variance_x <= 0.0 || variance_y <= 0.0 {
+        return None;
+    }
+    Some(covariance / (variance_x * variance_y).sqrt())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExamReliability {
+    #[serde(rename = "examId")]
+    pub exam_id: ObjectId,
+    /// Attempt-weighted mean over the generations
+    pub reliability: Reliability,
+    pub generations: Vec<GenerationReliability>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenerationReliability {
+    #[serde(rename = "generatedExamId")]
+    pub generated_exam_id: ObjectId,
+    /// Number of questions in the generation
+    #[serde(rename = "numQuestions")]
+    pub num_questions: usize,
+    pub reliability: Reliability,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Reliability {
+    /// Number of scored attempts
+    #[serde(rename = "numAttempts")]
+    pub num_attempts: usize,
+    /// Kuder-Richardson Formula 20 internal consistency of the number of correct answers
+    ///
+    /// `None` with fewer than 2 attempts or questions, or if every attempt has the same score.
+    pub kr20: Option<f64>,
+    /// Standard error of measurement, in percentage points of the score
+    #[serde(rename = "standardErrorOfMeasurement")]
+    pub standard_error_of_measurement: Option<f64>,
+    /// Estimated probability an attempt gets the same pass/fail decision on a parallel generation
+    #[serde(rename = "classificationConsistency")]
+    pub classification_consistency: Option<f64>,
+}
+
+/// Gets the reliability of each generation of an exam from the dichotomous `is_correct` of `calculate_score`,
+/// and their attempt-weighted mean for the exam.
+///
+/// Attempts of other exams, or that cannot be scored, are ignored. Generations are ordered by id.
+pub fn get_exam_reliability(
+    exam: &prisma::ExamEnvironmentExam,
+    generated_exams: &[prisma::ExamEnvironmentGeneratedExam],
+    attempts: &[prisma::ExamEnvironmentExamAttempt],
+) -> ExamReliability {
+    let mut reports: BTreeMap<ObjectId, Vec<ScoreReport>> = BTreeMap::new();
+    for attempt in attempts.iter().filter(|a| a.exam_id == exam.id) {
+        let Some(generated_exam) = generated_exams
+            .iter()
+            .find(|ge| ge.id == attempt.generated_exam_id)
+        else {
+            continue;
+        };
+        match calculate_score(exam, generated_exam, attempt) {
+            Ok(report) => reports.entry(generated_exam.id).or_default().push(report),
+            Err(e) => {
+                tracing::warn!(attempt = %attempt.id, error = %e, "unable to calculate score");
+            }
+        }
+    }
+
+    let generations: Vec<GenerationReliability> = reports
+        .into_iter()
+        .map(|(generated_exam_id, reports)| GenerationReliability {
+            generated_exam_id,
+            num_questions: reports.first().map(|r| r.total).unwrap_or_default(),
+            reliability: get_reliability(&reports, exam.config.passing_percent),
+        })
+        .collect();
+
+    let weighted_mean = |value: fn(&Reliability) -> Option<f64>| {
+        let values: Vec<(f64, f64)> = generations
+            .iter()
+            .filter_map(|g| Some((value(&g.reliability)?, g.reliability.num_attempts as f64)))
+            .collect();
+        let weight: f64 = values.iter().map(|(_, w)| w).sum();
+        (weight > 0.0).then(|| values.iter().map(|(v, w)| v * w).sum::<f64>() / weight)
+    };
+    let reliability = Reliability {
+        num_attempts: generations.iter().map(|g| g.reliability.num_attempts).sum(),
+        kr20: weighted_mean(|r| r.kr20),
+        standard_error_of_measurement: weighted_mean(|r| r.standard_error_of_measurement),
+        classification_consistency: weighted_mean(|r| r.classification_consistency),
+    };
+
+    ExamReliability {
+        exam_id: exam.id,
+        reliability,
+        generations,
+    }
+}
+
+/// Gets the reliability of attempts of the same generation, from their score reports.
+///
+/// Classification consistency uses a normal approximation around each attempt's estimated true score,
+/// regressed towards the mean by the KR-20.
+pub fn get_reliability(reports: &[ScoreReport], passing_percent: f64) -> Reliability {
+    let num_attempts = reports.len();
+    let num_questions = reports.first().map(|r| r.total).unwrap_or_default();
+    if num_attempts < 2 || num_questions < 2 || reports.iter().any(|r| r.total != num_questions) {
+        return Reliability {
+            num_attempts,
+            ..Default::default()
+        };
+    }
+
+    // Percentage of each attempt answered correctly, and fraction of attempts answering each question correctly
+    let scores: Vec<f64> = reports
+        .iter()
+        .map(|r| r.correct as f64 / num_questions as f64 * 100.0)
+        .collect();
+    let mut p_values = vec![0.0; num_questions];
+    for report in reports {
+        let questions = report
+            .question_sets
+            .iter()
+            .flat_map(|qs| qs.questions.iter());
+        for (p, question) in p_values.iter_mut().zip(questions) {
+            if question.is_correct {
+                *p += 1.0 / num_attempts as f64;
+            }
+        }
+    }
+
+    let n = num_attempts as f64;
+    let k = num_questions as f64;
+    let mean = scores.iter().sum::<f64>() / n;
+    let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+    if variance <= 0.0 {
+        return Reliability {
+            num_attempts,
+            ..Default::default()
+        };
+    }
+    // Item variances are in number-correct units, so are scaled to percentages
+    let item_variance = p_values.iter().map(|p| p * (1.0 - p)).sum::<f64>() * (100.0 / k).powi(2);
+    let kr20 = k / (k - 1.0) * (1.0 - item_variance / variance);
+    let standard_error_of_measurement = variance.sqrt() * (1.0 - kr20.clamp(0.0, 1.0)).sqrt();
+
+    let classification_consistency = scores
+        .iter()
+        .map(|score| {
+            let true_score = mean + kr20.clamp(0.0, 1.0) * (score - mean);
+            let p_pass = if standard_error_of_measurement > 0.0 {
+                normal_cdf((true_score - passing_percent) / standard_error_of_measurement)
+            } else if true_score >= passing_percent {
+                1.0
+            } else {
+                0.0
+            };
+            p_pass.powi(2) + (1.0 - p_pass).powi(2)
+        })
+        .sum::<f64>()
+        / n;
+
+    Reliability {
+        num_attempts,
+        kr20: Some(kr20),
+        standard_error_of_measurement: Some(standard_error_of_measurement),
+        classification_consistency: Some(classification_consistency),
+    }
+}
+
+/// Standard normal cumulative distribution, using the Abramowitz and Stegun 7.1.26 approximation of `erf`
+fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - polynomial * (-x * x).exp();
+    if z >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{DateTime, oid::ObjectId};
+    use prisma::{ExamEnvironmentAnswer, ExamEnvironmentConfig};
+
+    use crate::{
+        attempt::{Attempt, AttemptQuestionSet, AttemptQuestionSetQuestion},
+        misc::{QuestionScore, QuestionSetScore, ScoreReport},
+    };
+
+    use super::{analyse_items, get_reliability};
+
+    /// Question with one correct and two incorrect answers
+    fn question() -> AttemptQuestionSetQuestion {
+        AttemptQuestionSetQuestion {
+            id: ObjectId::new(),
+            text: "question".to_string(),
+            tags: vec![],
+            deprecated: false,
+            audio: None,
+            answers: [true, false, false]
+                .iter()
+                .map(|is_correct| ExamEnvironmentAnswer {
+                    id: ObjectId::new(),
+                    is_correct: *is_correct,
+                    text: "answer".to_string(),
+                })
+                .collect(),
+            selected: vec![],
+            generated: vec![],
+            submission_time: None,
+            dwell_time_in_s: 0.0,
+            visit_order: vec![],
+        }
+    }
+
+    /// Attempt generating the first two answers of the first two questions, selecting the given answer index
+    fn attempt(
+        exam_id: ObjectId,
+        question_set_id: ObjectId,
+        questions: &[AttemptQuestionSetQuestion],
+        selected: [Option<usize>; 2],
+    ) -> Attempt {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let mut questions = questions.to_vec();
+        for (question, selected) in questions.iter_mut().zip(selected) {
+            question.generated = question.answers[..2].iter().map(|a| a.id).collect();
+            if let Some(i) = selected {
+                question.selected = vec![question.answers[i].id];
+                question.submission_time = Some(start_time);
+            }
+        }
+
+        Attempt {
+            id: ObjectId::new(),
+            exam_id,
+            generated_exam_id: ObjectId::new(),
+            user_id: ObjectId::new(),
+            prerequisites: vec![],
+            deprecated: false,
+            question_sets: vec![AttemptQuestionSet {
+                id: question_set_id,
+                _type: Default::default(),
+                context: None,
+                questions,
+            }],
+            config: ExamEnvironmentConfig::default(),
+            start_time,
+        }
+    }
+
+    #[test]
+    fn item_analysis() {
+        let exam_id = ObjectId::new();
+        let question_set_id = ObjectId::new();
+        let questions = [question(), question(), question()];
+        let mut attempts = vec![
+            attempt(exam_id, question_set_id, &questions, [Some(0), Some(0)]),
+            attempt(exam_id, question_set_id, &questions, [Some(0), Some(0)]),
+            attempt(exam_id, question_set_id, &questions, [Some(1), Some(1)]),
+            attempt(exam_id, question_set_id, &questions, [Some(1), None]),
+        ];
+        // Attempts of other exams are ignored
+        attempts.push(attempt(
+            ObjectId::new(),
+            question_set_id,
+            &questions,
+            [Some(0), Some(1)],
+        ));
+
+        let analysis = analyse_items(exam_id, &attempts);
+        assert_eq!(analysis.num_attempts, 4);
+        assert_eq!(analysis.questions.len(), 3);
+
+        let summary: Vec<(usize, usize, Option<f64>, Option<f64>)> = analysis
+            .questions
+            .iter()
+            .map(|q| {
+                (
+                    q.exposure_count,
+                    q.answered_count,
+                    q.difficulty,
+                    q.point_biserial.map(|r| (r * 1e9).round() / 1e9),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (4, 4, Some(0.5), Some(1.0)),
+                (4, 3, Some(0.5), Some(1.0)),
+                // Never generated
+                (0, 0, None, None),
+            ]
+        );
+        assert!(
+            analysis
+                .questions
+                .iter()
+                .all(|q| q.question_set_id == question_set_id)
+        );
+
+        let answers: Vec<(bool, usize, usize, Option<f64>)> = analysis.questions[1]
+            .answers
+            .iter()
+            .map(|a| {
+                (
+                    a.is_correct,
+                    a.exposure_count,
+                    a.selection_count,
+                    a.selection_rate,
+                )
+            })
+            .collect();
+        assert_eq!(
+            answers,
+            vec![
+                (true, 4, 2, Some(0.5)),
+                (false, 4, 1, Some(0.25)),
+                (false, 0, 0, None),
+            ]
+        );
+    }
+
+    fn score_report(results: &[bool]) -> ScoreReport {
+        let questions: Vec<QuestionScore> = results
+            .iter()
+            .map(|is_correct| QuestionScore {
+                id: ObjectId::new(),
+                answered: true,
+                is_correct: *is_correct,
+                credit: if *is_correct { 1.0 } else { 0.0 },
+                weight: 1.0,
+            })
+            .collect();
+        let correct = results.iter().filter(|c| **c).count();
+        ScoreReport {
+            correct,
+            total: results.len(),
+            score: correct as f64,
+            max_score: results.len() as f64,
+            percentage: correct as f64 / results.len() as f64 * 100.0,
+            passing_percent: 50.0,
+            passed: correct * 2 >= results.len(),
+            question_sets: vec![QuestionSetScore {
+                id: ObjectId::new(),
+                correct,
+                total: results.len(),
+                score: correct as f64,
+                max_score: results.len() as f64,
+                questions,
+            }],
+        }
+    }
+
+    #[test]
+    fn reliability() {
+        // Perfect Guttman pattern: p-values 0.8, 0.6, 0.4, 0.2 and number-correct variance 2
+        let reports: Vec<ScoreReport> = (0..5)
+            .map(|correct| score_report(&[correct > 0, correct > 1, correct > 2, correct > 3]))
+            .collect();
+
+        let reliability = get_reliability(&reports, 50.0);
+        assert_eq!(reliability.num_attempts, 5);
+        // 4/3 * (1 - 0.8 / 2)
+        assert!((reliability.kr20.unwrap() - 0.8).abs() < 1e-9);
+        // sqrt(2) * sqrt(1 - 0.8) questions, at 25 percentage points per question
+        let sem = reliability.standard_error_of_measurement.unwrap();
+        assert!((sem - 25.0 * 0.4_f64.sqrt()).abs() < 1e-9);
+        let classification_consistency = reliability.classification_consistency.unwrap();
+        assert!((0.5..=1.0).contains(&classification_consistency));
+
+        // Equal scores have no variance
+        let reports = vec![score_report(&[true, false]), score_report(&[false, true])];
+        let reliability = get_reliability(&reports, 50.0);
+        assert_eq!(reliability.num_attempts, 2);
+        assert!(reliability.kr20.is_none());
+        assert!(reliability.classification_consistency.is_none());
+    }
+}
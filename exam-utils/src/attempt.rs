@@ -6,10 +6,128 @@ use prisma::{
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-use crate::error::Error;
+use crate::{error::Error, misc::compare_answers};
 
-pub fn get_time_between_submissions(_attempt: prisma::ExamEnvironmentExamAttempt) -> Vec<Duration> {
-    todo!()
+/// Submissions separated by less than this are considered part of a burst
+pub const BURST_MAX_GAP: Duration = Duration::from_secs(1);
+/// Minimum number of submissions in a row to be considered a burst
+pub const BURST_MIN_SUBMISSIONS: usize = 3;
+
+/// Gets the time between consecutive submissions, ordered by submission time.
+pub fn get_time_between_submissions(attempt: prisma::ExamEnvironmentExamAttempt) -> Vec<Duration> {
+    let submission_times = attempt
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter().map(|q| q.submission_time))
+        .collect();
+
+    get_gaps(submission_times)
+}
+
+fn get_gaps(mut submission_times: Vec<mongodb::bson::DateTime>) -> Vec<Duration> {
+    submission_times.sort();
+
+    submission_times
+        .windows(2)
+        .map(|w| w[1].saturating_duration_since(w[0]))
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SubmissionGapStats {
+    /// Number of gaps, i.e. one less than the number of submissions
+    pub count: usize,
+    #[serde(rename = "minInS")]
+    pub min_in_s: f64,
+    #[serde(rename = "medianInS")]
+    pub median_in_s: f64,
+    #[serde(rename = "p95InS")]
+    pub p95_in_s: f64,
+    /// Longest gap between two submissions
+    #[serde(rename = "longestGap")]
+    pub longest_gap: Option<SubmissionGap>,
+    /// Runs of at least `BURST_MIN_SUBMISSIONS` submissions, each less than `BURST_MAX_GAP` apart
+    pub bursts: Vec<SubmissionBurst>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmissionGap {
+    /// 0-based index of the submission the gap ends at
+    pub index: usize,
+    #[serde(rename = "durationInS")]
+    pub duration_in_s: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmissionBurst {
+    /// 0-based index of the first submission in the burst
+    pub start: usize,
+    /// Number of submissions in the burst
+    pub submissions: usize,
+    /// Time from the first to the last submission in the burst
+    #[serde(rename = "durationInS")]
+    pub duration_in_s: f64,
+}
+
+/// Summarises the gaps between submissions, as returned by `get_time_between_submissions`.
+///
+/// Percentiles use the nearest-rank method.
+pub fn get_submission_gap_stats(gaps: &[Duration]) -> SubmissionGapStats {
+    if gaps.is_empty() {
+        return SubmissionGapStats::default();
+    }
+
+    let mut sorted = gaps.to_vec();
+    sorted.sort();
+
+    let longest_gap = gaps
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, gap)| **gap)
+        .map(|(i, gap)| SubmissionGap {
+            index: i + 1,
+            duration_in_s: gap.as_secs_f64(),
+        });
+
+    let mut bursts = vec![];
+    let mut burst_start = 0;
+    let mut burst_duration = Duration::ZERO;
+    // Iterate one past the end to close a trailing burst
+    for i in 0..=gaps.len() {
+        match gaps.get(i) {
+            Some(gap) if *gap < BURST_MAX_GAP => {
+                burst_duration += *gap;
+            }
+            _ => {
+                // Gaps `burst_start..i` are all short, linking `i - burst_start + 1` submissions
+                let submissions = i - burst_start + 1;
+                if submissions >= BURST_MIN_SUBMISSIONS {
+                    bursts.push(SubmissionBurst {
+                        start: burst_start,
+                        submissions,
+                        duration_in_s: burst_duration.as_secs_f64(),
+                    });
+                }
+                burst_start = i + 1;
+                burst_duration = Duration::ZERO;
+            }
+        }
+    }
+
+    SubmissionGapStats {
+        count: gaps.len(),
+        min_in_s: sorted[0].as_secs_f64(),
+        median_in_s: percentile(&sorted, 50.0).as_secs_f64(),
+        p95_in_s: percentile(&sorted, 95.0).as_secs_f64(),
+        longest_gap,
+        bursts,
+    }
+}
+
+/// Nearest-rank percentile of a sorted, non-empty slice
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
 }
 
 #[serde_with::serde_as]
@@ -18,6 +136,8 @@ pub struct Attempt {
     pub id: ObjectId,
     #[serde(rename = "examId")]
     pub exam_id: ObjectId,
+    #[serde(rename = "generatedExamId")]
+    pub generated_exam_id: ObjectId,
     #[serde(rename = "userId")]
     pub user_id: ObjectId,
     pub prerequisites: Vec<ObjectId>,
@@ -55,6 +175,18 @@ pub struct AttemptQuestionSetQuestion {
     /// If question was submitted, time it was submitted
     #[serde(rename = "submissionTime")]
     pub submission_time: Option<mongodb::bson::DateTime>,
+    /// Time spent on the question, from `QuestionVisit` events. See `add_question_visits`.
+    #[serde(rename = "dwellTimeInS", default)]
+    pub dwell_time_in_s: f64,
+    /// Positions of the question in the order questions were visited. See `add_question_visits`.
+    #[serde(rename = "visitOrder", default)]
+    pub visit_order: Vec<usize>,
+}
+
+impl AttemptQuestionSetQuestion {
+    pub fn visit_count(&self) -> usize {
+        self.visit_order.len()
+    }
 }
 
 /// Constructs an `Attempt`:
@@ -62,8 +194,8 @@ pub struct AttemptQuestionSetQuestion {
 /// - Adds submission time from attempt questions
 /// - Adds selected answers from attempt
 ///
-/// NOTE: Generated exam is assumed to not be needed,
-/// because API ensures attempt only includes answers from assigned generation.
+/// NOTE: Attempt is assumed to only include answers from the assigned generation.
+/// Use `verify_attempt` to check this.
 pub fn construct_attempt(
     exam: &prisma::ExamEnvironmentExam,
     generation: &prisma::ExamEnvironmentGeneratedExam,
@@ -138,6 +270,8 @@ pub fn construct_attempt(
                 selected,
                 generated,
                 submission_time,
+                dwell_time_in_s: 0.0,
+                visit_order: vec![],
             };
 
             attempt_questions.push(attempt_question_set_question);
@@ -158,6 +292,7 @@ pub fn construct_attempt(
     let attempt = Attempt {
         id: exam_attempt.id,
         exam_id: exam_attempt.exam_id,
+        generated_exam_id: exam_attempt.generated_exam_id,
         user_id: exam_attempt.user_id,
         prerequisites: prerequisites.clone(),
         deprecated: *deprecated,
@@ -169,89 +304,1045 @@ pub fn construct_attempt(
     attempt
 }
 
+/// A way an attempt does not match the exam and generation it was assigned
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum IntegrityIssue {
+    /// Question set is not part of the exam
+    UnknownQuestionSet { question_set_id: ObjectId },
+    /// Question is not part of the question set in the generation
+    QuestionOutsideGeneration {
+        question_set_id: ObjectId,
+        question_id: ObjectId,
+    },
+    /// Answer is not one of the answers generated for the question
+    AnswerOutsideGeneration {
+        question_id: ObjectId,
+        answer_id: ObjectId,
+    },
+    /// More than one answer selected for a question with a single correct answer
+    MultipleAnswers {
+        question_id: ObjectId,
+        number_of_answers: usize,
+    },
+    /// Question submitted before the attempt started
+    SubmissionBeforeStart {
+        question_id: ObjectId,
+        submission_time: mongodb::bson::DateTime,
+    },
+    /// Question submitted after the exam time ran out
+    SubmissionAfterEnd {
+        question_id: ObjectId,
+        submission_time: mongodb::bson::DateTime,
+    },
+}
+
+impl std::fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityIssue::UnknownQuestionSet { question_set_id } => {
+                write!(f, "Question set {question_set_id} is not in the exam")
+            }
+            IntegrityIssue::QuestionOutsideGeneration {
+                question_set_id,
+                question_id,
+            } => write!(
+                f,
+                "Question {question_id} is not in question set {question_set_id} of the generation"
+            ),
+            IntegrityIssue::AnswerOutsideGeneration {
+                question_id,
+                answer_id,
+            } => write!(
+                f,
+                "Answer {answer_id} is not generated for question {question_id}"
+            ),
+            IntegrityIssue::MultipleAnswers {
+                question_id,
+                number_of_answers,
+            } => write!(
+                f,
+                "{number_of_answers} answers selected for single-answer question {question_id}"
+            ),
+            IntegrityIssue::SubmissionBeforeStart {
+                question_id,
+                submission_time,
+            } => write!(
+                f,
+                "Question {question_id} submitted at {submission_time} before the attempt started"
+            ),
+            IntegrityIssue::SubmissionAfterEnd {
+                question_id,
+                submission_time,
+            } => write!(
+                f,
+                "Question {question_id} submitted at {submission_time} after the exam ended"
+            ),
+        }
+    }
+}
+
+/// Checks the attempt only includes questions and answers from its assigned generation,
+/// submitted within the exam time.
+///
+/// Questions outside the generation are not checked any further.
+pub fn verify_attempt(
+    exam: &prisma::ExamEnvironmentExam,
+    generation: &prisma::ExamEnvironmentGeneratedExam,
+    exam_attempt: &prisma::ExamEnvironmentExamAttempt,
+) -> Vec<IntegrityIssue> {
+    let start_time = exam_attempt.start_time;
+    let end_time = mongodb::bson::DateTime::from_millis(
+        start_time.timestamp_millis() + exam.config.total_time_in_s * 1000,
+    );
+
+    let mut issues = vec![];
+
+    for attempt_question_set in exam_attempt.question_sets.iter() {
+        let Some(question_set) = exam
+            .question_sets
+            .iter()
+            .find(|qs| qs.id == attempt_question_set.id)
+        else {
+            issues.push(IntegrityIssue::UnknownQuestionSet {
+                question_set_id: attempt_question_set.id,
+            });
+            continue;
+        };
+        let generation_question_set = generation
+            .question_sets
+            .iter()
+            .find(|qs| qs.id == attempt_question_set.id);
+
+        for attempt_question in attempt_question_set.questions.iter() {
+            let Some(generation_question) = generation_question_set
+                .and_then(|gqs| gqs.questions.iter().find(|q| q.id == attempt_question.id))
+            else {
+                issues.push(IntegrityIssue::QuestionOutsideGeneration {
+                    question_set_id: attempt_question_set.id,
+                    question_id: attempt_question.id,
+                });
+                continue;
+            };
+
+            for answer_id in attempt_question.answers.iter() {
+                if !generation_question.answers.contains(answer_id) {
+                    issues.push(IntegrityIssue::AnswerOutsideGeneration {
+                        question_id: attempt_question.id,
+                        answer_id: *answer_id,
+                    });
+                }
+            }
+
+            let number_of_correct_answers = question_set
+                .questions
+                .iter()
+                .find(|q| q.id == attempt_question.id)
+                .map(|q| {
+                    q.answers
+                        .iter()
+                        .filter(|a| a.is_correct && generation_question.answers.contains(&a.id))
+                        .count()
+                })
+                .unwrap_or_default();
+            if number_of_correct_answers == 1 && attempt_question.answers.len() > 1 {
+                issues.push(IntegrityIssue::MultipleAnswers {
+                    question_id: attempt_question.id,
+                    number_of_answers: attempt_question.answers.len(),
+                });
+            }
+
+            let submission_time = attempt_question.submission_time;
+            if submission_time < start_time {
+                issues.push(IntegrityIssue::SubmissionBeforeStart {
+                    question_id: attempt_question.id,
+                    submission_time,
+                });
+            } else if submission_time > end_time {
+                issues.push(IntegrityIssue::SubmissionAfterEnd {
+                    question_id: attempt_question.id,
+                    submission_time,
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AttemptStats {
+    /// Time taken for each submission, ordered by submission time
+    #[serde(rename = "timeToAnswers")]
     pub time_to_answers: Vec<TimeToAnswer>,
+    /// Number of questions in the generation
+    #[serde(rename = "totalQuestions")]
     pub total_questions: usize,
     pub answered: usize,
     pub correct: usize,
+    /// Seconds from the start of the attempt until the last submission
+    #[serde(rename = "timeToComplete")]
     pub time_to_complete: f64,
+    #[serde(rename = "averageTimePerQuestion")]
     pub average_time_per_question: f64,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TimeToAnswer {
+    /// 1-based position of the submission in the attempt
     pub name: usize,
+    /// Seconds since the previous submission, or since the start of the attempt for the first submission
     pub value: f64,
+    #[serde(rename = "isCorrect")]
     pub is_correct: bool,
 }
 
-pub fn get_attempt_stats(_attempt: Attempt) -> AttemptStats {
-    todo!()
+/// Calculates the answer counts and timings of an attempt.
+///
+/// Questions are considered answered if they have a submission time,
+/// and correct if the selected answers match the correct generated answers.
+pub fn get_attempt_stats(attempt: Attempt) -> AttemptStats {
+    let mut total_questions = 0;
+    let mut submissions = vec![];
+
+    for question in attempt
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+    {
+        if !question.generated.is_empty() {
+            total_questions += 1;
+        }
+
+        if let Some(submission_time) = question.submission_time {
+            let is_correct =
+                compare_answers(&question.answers, &question.generated, &question.selected);
+            submissions.push((submission_time, is_correct));
+        }
+    }
+
+    submissions.sort_by_key(|(submission_time, _)| *submission_time);
+
+    let mut time_to_answers = Vec::with_capacity(submissions.len());
+    let mut previous_submission_time = attempt.start_time;
+    for (i, (submission_time, is_correct)) in submissions.iter().enumerate() {
+        let value = submission_time
+            .saturating_duration_since(previous_submission_time)
+            .as_secs_f64();
+        time_to_answers.push(TimeToAnswer {
+            name: i + 1,
+            value,
+            is_correct: *is_correct,
+        });
+        previous_submission_time = *submission_time;
+    }
+
+    let answered = submissions.len();
+    let correct = submissions
+        .iter()
+        .filter(|(_, is_correct)| *is_correct)
+        .count();
+
+    let time_to_complete = previous_submission_time
+        .saturating_duration_since(attempt.start_time)
+        .as_secs_f64();
+    let average_time_per_question = if answered == 0 {
+        0.0
+    } else {
+        time_to_complete / answered as f64
+    };
+
+    AttemptStats {
+        time_to_answers,
+        total_questions,
+        answered,
+        correct,
+        time_to_complete,
+        average_time_per_question,
+    }
+}
+
+/// A heuristic contributing to the moderation score.
+pub trait ModerationSignal: Send + Sync {
+    /// Identifier used in logs
+    fn name(&self) -> &'static str;
+
+    /// Calculates a 0.0 -> 1.0 value.
+    ///
+    /// - A value of 0.0 means the signal found nothing suspicious.
+    /// - A value of 1.0 means the signal found the attempt as suspicious as it can.
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error>;
+}
+
+pub struct WeightedSignal {
+    pub signal: Box<dyn ModerationSignal>,
+    /// Relative weight of the signal. Weights are normalized by their total.
+    pub weight: f64,
+}
+
+/// Combines a set of weighted signals into a moderation score.
+pub struct ModerationScorer {
+    pub signals: Vec<WeightedSignal>,
+}
+
+impl Default for ModerationScorer {
+    /// Time remaining, total blur time, blur time before the last answer, number of sessions, time outside the exam,
+    /// answers to questions barely looked at, and audio questions answered faster than they could be heard.
+    ///
+    /// Blur time before the last answer is worth more, as blurring whilst answering is more suspicious.
+    ///
+    /// The time remaining and blur signals keep their original 1:1:2 ratio, and carry half of the total weight.
+    fn default() -> Self {
+        Self::new()
+            .with_signal(TimeRemainingSignal, 0.125)
+            .with_signal(BlurTimeSignal, 0.125)
+            .with_signal(BlurBeforeLastAnswerSignal, 0.25)
+            .with_signal(SessionCountSignal, 0.15)
+            .with_signal(TimeOutsideSignal, 0.15)
+            .with_signal(LowDwellTimeSignal::default(), 0.1)
+            .with_signal(AudioListeningSignal, 0.1)
+    }
+}
+
+impl ModerationScorer {
+    /// Creates a scorer without any signals.
+    pub fn new() -> Self {
+        Self { signals: vec![] }
+    }
+
+    pub fn with_signal<S>(mut self, signal: S, weight: f64) -> Self
+    where
+        S: ModerationSignal + 'static,
+    {
+        self.signals.push(WeightedSignal {
+            signal: Box::new(signal),
+            weight,
+        });
+        self
+    }
+
+    /// Calculates a 0.0 -> 1.0 score as the weighted average of all signal values.
+    pub fn score(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        self.report(attempt, events).map(|report| report.score)
+    }
+
+    /// Calculates the moderation score, along with each signal's contribution to it.
+    pub fn report(&self, attempt: &Attempt, events: &[Event]) -> Result<ModerationReport, Error> {
+        let total_weight: f64 = self.signals.iter().map(|s| s.weight).sum();
+        if let Some(s) = self
+            .signals
+            .iter()
+            .find(|s| !s.weight.is_finite() || s.weight < 0.0)
+        {
+            return Err(Error::ModerationScore(format!(
+                "{} weight must be a finite, non-negative number: {}",
+                s.signal.name(),
+                s.weight
+            )));
+        }
+        if total_weight <= 0.0 {
+            return Err(Error::ModerationScore(format!(
+                "total signal weight must be > 0: {total_weight}"
+            )));
+        }
+
+        let mut report = ModerationReport {
+            score: 0.0,
+            components: vec![],
+            total_blur_time_in_s: 0.0,
+            total_blur_time_before_last_answer_in_s: 0.0,
+            inferred_blur_intervals: 0,
+            sessions: 0,
+            time_outside_in_s: 0.0,
+            total_time_taken_in_s: 0.0,
+            total_time_in_s: attempt.config.total_time_in_s as f64,
+        };
+
+        if get_last_submission_time(attempt).is_none() {
+            // Theoretically, this should be impossible -> function currently only called if attempt passes
+            tracing::warn!(attempt = %attempt.id, "attempt did not submit any answers");
+            return Ok(report);
+        }
+
+        let BlurTimes {
+            total_blur_time,
+            total_blur_time_before_last_answer,
+            inferred_intervals,
+        } = get_blur_times(attempt, events)?;
+        report.total_blur_time_in_s = total_blur_time;
+        report.total_blur_time_before_last_answer_in_s = total_blur_time_before_last_answer;
+        report.inferred_blur_intervals = inferred_intervals;
+        report.total_time_taken_in_s = get_total_time_taken(attempt)?;
+
+        let AttemptSessions {
+            sessions,
+            time_outside_in_s,
+        } = get_attempt_sessions(attempt, events);
+        report.sessions = sessions.len();
+        report.time_outside_in_s = time_outside_in_s;
+
+        for WeightedSignal { signal, weight } in &self.signals {
+            let value = signal.value(attempt, events)?;
+            if !(0.0..=1.0).contains(&value) {
+                return Err(Error::ModerationScore(format!(
+                    "{} value must be between 0.0 and 1.0: {value}",
+                    signal.name()
+                )));
+            }
+            let weight = weight / total_weight;
+            let contribution = value * weight;
+            report.score += contribution;
+            report.components.push(ModerationComponent {
+                name: signal.name().to_string(),
+                value,
+                weight,
+                contribution,
+            });
+        }
+
+        if report.score > 1.0 {
+            tracing::error!(
+                attempt = %attempt.id,
+                moderation_score = report.score,
+                "moderation score should never be > 1.0"
+            );
+        }
+
+        Ok(report)
+    }
+}
+
+/// Breakdown of a moderation score
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModerationReport {
+    pub score: f64,
+    pub components: Vec<ModerationComponent>,
+    #[serde(rename = "totalBlurTimeInS")]
+    pub total_blur_time_in_s: f64,
+    #[serde(rename = "totalBlurTimeBeforeLastAnswerInS")]
+    pub total_blur_time_before_last_answer_in_s: f64,
+    /// Number of blur intervals without a matching `Focus` event
+    #[serde(rename = "inferredBlurIntervals")]
+    pub inferred_blur_intervals: usize,
+    /// Number of sessions separated by `ExamExit` events
+    pub sessions: usize,
+    /// Time between exiting and re-entering the exam
+    #[serde(rename = "timeOutsideInS")]
+    pub time_outside_in_s: f64,
+    /// Time from the start of the attempt until the last submission
+    #[serde(rename = "totalTimeTakenInS")]
+    pub total_time_taken_in_s: f64,
+    #[serde(rename = "totalTimeInS")]
+    pub total_time_in_s: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModerationComponent {
+    /// Name of the signal
+    pub name: String,
+    /// 0.0 -> 1.0 value of the signal
+    pub value: f64,
+    /// Weight of the signal, normalized so all weights add up to 1.0
+    pub weight: f64,
+    /// `value * weight`
+    pub contribution: f64,
+}
+
+impl std::fmt::Display for ModerationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Moderation score: {:.3}", self.score)?;
+        for component in &self.components {
+            writeln!(
+                f,
+                "- {}: {:.3} x {:.3} = {:.3}",
+                component.name, component.value, component.weight, component.contribution
+            )?;
+        }
+        write!(
+            f,
+            "Blur time: {:.0}s ({:.0}s before last answer, {} inferred intervals). Sessions: {} ({:.0}s outside exam). Time taken: {:.0}s of {:.0}s.",
+            self.total_blur_time_in_s,
+            self.total_blur_time_before_last_answer_in_s,
+            self.inferred_blur_intervals,
+            self.sessions,
+            self.time_outside_in_s,
+            self.total_time_taken_in_s,
+            self.total_time_in_s
+        )
+    }
 }
 
-/// Calculates a 0.0 -> 1.0 score.
+/// Calculates a 0.0 -> 1.0 score using the default `ModerationScorer`.
 ///
 /// - A score of 0.0 means the attempt definitely does **not** need moderation.
 /// - A score of 1.0 means the attempt definitely does need moderation.
-pub fn get_moderation_score(attempt: &Attempt, events: &Vec<Event>) -> Result<f64, Error> {
-    // (1 / number of parts)
-    let weight = 0.25;
-    let mut moderation_score = 0.0;
-    let mut total_blur_time = 0.0;
-    let mut total_blur_time_before_last_answer = 0.0;
+pub fn get_moderation_score(attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+    ModerationScorer::default().score(attempt, events)
+}
 
-    let mut events = events.clone();
-    events.sort_by(|a, b| (a.timestamp).cmp(&b.timestamp));
+/// Calculates the moderation score using the default `ModerationScorer`, along with the breakdown of how it was reached.
+pub fn get_moderation_report(
+    attempt: &Attempt,
+    events: &[Event],
+) -> Result<ModerationReport, Error> {
+    ModerationScorer::default().report(attempt, events)
+}
 
-    let last_submission_time = attempt
+fn get_last_submission_time(attempt: &Attempt) -> Option<mongodb::bson::DateTime> {
+    attempt
         .question_sets
         .iter()
         .flat_map(|qs| qs.questions.iter().flat_map(|q| q.submission_time))
-        .max();
+        .max()
+}
 
-    let last_submission_time = match last_submission_time {
-        Some(last_submission_time) => last_submission_time,
-        None => {
-            // Theoretically, this should be impossible -> function currently only called if attempt passes
-            tracing::warn!(attempt = %attempt.id, "attempt did not submit any answers");
-            return Ok(moderation_score);
+fn get_total_time(attempt: &Attempt) -> Result<f64, Error> {
+    let total_time = attempt.config.total_time_in_s as f64;
+    if total_time <= 0.0 {
+        return Err(Error::ModerationScore(format!(
+            "total time must be > 0: {total_time}"
+        )));
+    }
+
+    Ok(total_time)
+}
+
+/// Time taken to answer all questions -> does not include checking over answers / waiting before exiting
+fn get_total_time_taken(attempt: &Attempt) -> Result<f64, Error> {
+    let total_time_taken = match get_last_submission_time(attempt) {
+        Some(last_submission_time) => last_submission_time
+            .saturating_duration_since(attempt.start_time)
+            .as_secs_f64(),
+        None => 0.0,
+    };
+
+    let total_time = get_total_time(attempt)?;
+    if total_time_taken > total_time {
+        return Err(Error::ModerationScore(format!(
+            "total time taken > than total time: {} > {}",
+            total_time_taken, total_time
+        )));
+    }
+
+    Ok(total_time_taken)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum BlurIntervalEnd {
+    /// Exam regained focus
+    Focus,
+    /// Exam was exited whilst out of focus
+    ExamExit,
+    /// No focus recorded before the last submission, which requires focus
+    LastSubmission,
+    /// No focus recorded before the exam ended
+    ExamEnd,
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlurInterval {
+    #[serde(rename = "startTime")]
+    #[serde_as(as = "bson::serde_helpers::datetime::AsRfc3339String")]
+    pub start_time: mongodb::bson::DateTime,
+    #[serde(rename = "endTime")]
+    #[serde_as(as = "bson::serde_helpers::datetime::AsRfc3339String")]
+    pub end_time: mongodb::bson::DateTime,
+    pub end: BlurIntervalEnd,
+}
+
+impl BlurInterval {
+    /// Whether the end of the interval was inferred, because no `Focus` event closed it
+    pub fn is_inferred(&self) -> bool {
+        self.end != BlurIntervalEnd::Focus
+    }
+
+    pub fn duration_in_s(&self) -> f64 {
+        self.end_time
+            .saturating_duration_since(self.start_time)
+            .as_secs_f64()
+    }
+}
+
+enum FocusState {
+    Focused,
+    Blurred(mongodb::bson::DateTime),
+}
+
+/// Gets the intervals the exam was out of focus, clamped to the exam time.
+///
+/// A `Blur` is closed by the next `Focus` or `ExamExit`. Repeated `Blur` events extend the open interval.
+/// An interval left open after all events is closed at the last submission if it started before it,
+/// otherwise at the end of the exam time.
+pub fn get_blur_intervals(attempt: &Attempt, events: &[Event]) -> Vec<BlurInterval> {
+    let exam_start = attempt.start_time;
+    let exam_end = mongodb::bson::DateTime::from_millis(
+        exam_start.timestamp_millis() + attempt.config.total_time_in_s * 1000,
+    );
+
+    let mut events = events.to_vec();
+    events.sort_by_key(|e| e.timestamp);
+
+    let mut intervals = vec![];
+    let mut push_interval = |start_time: mongodb::bson::DateTime,
+                             end_time: mongodb::bson::DateTime,
+                             end: BlurIntervalEnd| {
+        let start_time = start_time.max(exam_start);
+        let end_time = end_time.min(exam_end);
+        if start_time < end_time {
+            intervals.push(BlurInterval {
+                start_time,
+                end_time,
+                end,
+            });
         }
     };
 
-    let mut previous_blur_time = None;
+    let mut state = FocusState::Focused;
     for event in events {
-        let timestamp = event.timestamp;
-        match event.kind {
-            EventKind::Blur => {
-                previous_blur_time = Some(timestamp);
+        let timestamp = mongodb::bson::DateTime::from_millis(event.timestamp.timestamp_millis());
+        state = match (state, event.kind) {
+            (FocusState::Focused, EventKind::Blur) => FocusState::Blurred(timestamp),
+            (FocusState::Blurred(start_time), EventKind::Focus) => {
+                push_interval(start_time, timestamp, BlurIntervalEnd::Focus);
+                FocusState::Focused
+            }
+            (FocusState::Blurred(start_time), EventKind::ExamExit) => {
+                push_interval(start_time, timestamp, BlurIntervalEnd::ExamExit);
+                FocusState::Focused
+            }
+            (state, _) => state,
+        };
+    }
+
+    if let FocusState::Blurred(start_time) = state {
+        match get_last_submission_time(attempt) {
+            Some(last_submission_time) if start_time < last_submission_time => {
+                push_interval(
+                    start_time,
+                    last_submission_time,
+                    BlurIntervalEnd::LastSubmission,
+                );
             }
-            EventKind::Focus => {
-                if let Some(previous_blur_time) = previous_blur_time {
-                    let blur_time = (timestamp - previous_blur_time).as_seconds_f64();
-                    total_blur_time += blur_time;
+            _ => push_interval(start_time, exam_end, BlurIntervalEnd::ExamEnd),
+        }
+    }
+
+    intervals
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttemptSession {
+    #[serde(rename = "startTime")]
+    #[serde_as(as = "bson::serde_helpers::datetime::AsRfc3339String")]
+    pub start_time: mongodb::bson::DateTime,
+    /// Time of the `ExamExit`, or of the last activity if the session was not exited
+    #[serde(rename = "endTime")]
+    #[serde_as(as = "bson::serde_helpers::datetime::AsRfc3339String")]
+    pub end_time: mongodb::bson::DateTime,
+    pub exited: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttemptSessions {
+    pub sessions: Vec<AttemptSession>,
+    /// Time between each `ExamExit` and the next activity
+    #[serde(rename = "timeOutsideInS")]
+    pub time_outside_in_s: f64,
+}
+
+/// Splits an attempt into sessions separated by `ExamExit` events.
+///
+/// A session is re-entered on the first event or submission after an exit.
+/// Activity outside of the exam time is ignored.
+pub fn get_attempt_sessions(attempt: &Attempt, events: &[Event]) -> AttemptSessions {
+    let exam_start = attempt.start_time;
+    let exam_end = mongodb::bson::DateTime::from_millis(
+        exam_start.timestamp_millis() + attempt.config.total_time_in_s * 1000,
+    );
+
+    // (timestamp, is exit)
+    let mut activity: Vec<(mongodb::bson::DateTime, bool)> = events
+        .iter()
+        .map(|e| {
+            let timestamp = mongodb::bson::DateTime::from_millis(e.timestamp.timestamp_millis());
+            (timestamp, matches!(e.kind, EventKind::ExamExit))
+        })
+        .chain(
+            attempt
+                .question_sets
+                .iter()
+                .flat_map(|qs| qs.questions.iter().flat_map(|q| q.submission_time))
+                .map(|submission_time| (submission_time, false)),
+        )
+        .filter(|(timestamp, _)| *timestamp >= exam_start && *timestamp <= exam_end)
+        .collect();
+    activity.sort();
+
+    let mut sessions = vec![];
+    let mut time_outside_in_s = 0.0;
+    let mut session_start = Some(exam_start);
+    let mut last_activity = exam_start;
+    let mut last_exit = exam_start;
+
+    for (timestamp, is_exit) in activity {
+        match (session_start, is_exit) {
+            (Some(start_time), true) => {
+                sessions.push(AttemptSession {
+                    start_time,
+                    end_time: timestamp,
+                    exited: true,
+                });
+                session_start = None;
+                last_exit = timestamp;
+            }
+            (None, false) => {
+                time_outside_in_s += timestamp.saturating_duration_since(last_exit).as_secs_f64();
+                session_start = Some(timestamp);
+            }
+            // Repeated exits, or activity within a session
+            _ => {}
+        }
+        last_activity = timestamp;
+    }
+
+    if let Some(start_time) = session_start {
+        sessions.push(AttemptSession {
+            start_time,
+            end_time: last_activity,
+            exited: false,
+        });
+    }
 
-                    if timestamp.timestamp_millis() < last_submission_time.timestamp_millis() {
-                        total_blur_time_before_last_answer += blur_time;
+    AttemptSessions {
+        sessions,
+        time_outside_in_s,
+    }
+}
+
+/// `meta` of a `QuestionVisit` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionVisitMeta {
+    #[serde(rename = "questionId", alias = "question_id")]
+    pub question_id: ObjectId,
+}
+
+impl TryFrom<&Event> for QuestionVisitMeta {
+    type Error = Error;
+
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        if !matches!(event.kind, EventKind::QuestionVisit) {
+            return Err(Error::EventMeta(format!(
+                "event {} is not a question visit: {:?}",
+                event.id, event.kind
+            )));
+        }
+
+        serde_json::from_value(event.meta.clone()).map_err(|e| {
+            Error::EventMeta(format!(
+                "unable to parse question visit meta for event {}: {e}",
+                event.id
+            ))
+        })
+    }
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionVisit {
+    #[serde(rename = "questionId")]
+    pub question_id: ObjectId,
+    #[serde(rename = "startTime")]
+    #[serde_as(as = "bson::serde_helpers::datetime::AsRfc3339String")]
+    pub start_time: mongodb::bson::DateTime,
+    #[serde(rename = "endTime")]
+    #[serde_as(as = "bson::serde_helpers::datetime::AsRfc3339String")]
+    pub end_time: mongodb::bson::DateTime,
+}
+
+impl QuestionVisit {
+    pub fn duration_in_s(&self) -> f64 {
+        self.end_time
+            .saturating_duration_since(self.start_time)
+            .as_secs_f64()
+    }
+}
+
+/// Gets the question visits of an attempt, in navigation order.
+///
+/// A visit lasts until the next `QuestionVisit` or `ExamExit`.
+/// The last visit lasts until the last event or submission, whichever is later.
+/// Visits are clamped to the exam time, and `QuestionVisit` events with invalid `meta` are skipped.
+pub fn get_question_visits(attempt: &Attempt, events: &[Event]) -> Vec<QuestionVisit> {
+    let exam_start = attempt.start_time;
+    let exam_end = mongodb::bson::DateTime::from_millis(
+        exam_start.timestamp_millis() + attempt.config.total_time_in_s * 1000,
+    );
+
+    let mut events = events.to_vec();
+    events.sort_by_key(|e| e.timestamp);
+
+    let mut visits = vec![];
+    let mut push_visit = |question_id: ObjectId,
+                          start_time: mongodb::bson::DateTime,
+                          end_time: mongodb::bson::DateTime| {
+        let start_time = start_time.max(exam_start);
+        let end_time = end_time.min(exam_end);
+        if start_time <= end_time {
+            visits.push(QuestionVisit {
+                question_id,
+                start_time,
+                end_time,
+            });
+        }
+    };
+
+    let mut current_visit: Option<(ObjectId, mongodb::bson::DateTime)> = None;
+    let mut last_activity = get_last_submission_time(attempt).unwrap_or(exam_start);
+    for event in &events {
+        let timestamp = mongodb::bson::DateTime::from_millis(event.timestamp.timestamp_millis());
+        last_activity = last_activity.max(timestamp);
+
+        match event.kind {
+            EventKind::QuestionVisit => {
+                let QuestionVisitMeta { question_id } = match QuestionVisitMeta::try_from(event) {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        tracing::warn!(attempt = %attempt.id, error = %e, "skipping question visit");
+                        continue;
                     }
+                };
+                if let Some((previous_question_id, start_time)) = current_visit {
+                    push_visit(previous_question_id, start_time, timestamp);
+                }
+                current_visit = Some((question_id, timestamp));
+            }
+            EventKind::ExamExit => {
+                if let Some((previous_question_id, start_time)) = current_visit.take() {
+                    push_visit(previous_question_id, start_time, timestamp);
                 }
             }
-
             _ => {}
         }
     }
 
-    // Time taken to answer all questions -> does not include checking over answers / waiting before exiting
-    let total_time_taken = last_submission_time
-        .saturating_duration_since(attempt.start_time)
-        .as_secs_f64();
+    if let Some((question_id, start_time)) = current_visit {
+        push_visit(question_id, start_time, last_activity);
+    }
 
-    let total_time = attempt.config.total_time_in_s as f64;
+    visits
+}
 
-    if total_time_taken > total_time {
-        return Err(Error::ModerationScore(format!(
-            "total time taken > than total time: {} > {}",
-            total_time_taken, total_time
-        )));
+/// Adds the dwell time, visit count, and visit order of each question from its `QuestionVisit` events.
+pub fn add_question_visits(attempt: &mut Attempt, events: &[Event]) {
+    let visits = get_question_visits(attempt, events);
+
+    for question in attempt
+        .question_sets
+        .iter_mut()
+        .flat_map(|qs| qs.questions.iter_mut())
+    {
+        question.dwell_time_in_s = 0.0;
+        question.visit_order.clear();
+
+        for (i, visit) in visits.iter().enumerate() {
+            if visit.question_id == question.id {
+                question.dwell_time_in_s += visit.duration_in_s();
+                question.visit_order.push(i);
+            }
+        }
+    }
+}
+
+/// `meta` of a `CaptionsOpened` event
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaptionsOpenedMeta {
+    #[serde(rename = "questionId", alias = "question_id")]
+    pub question_id: ObjectId,
+}
+
+impl TryFrom<&Event> for CaptionsOpenedMeta {
+    type Error = Error;
+
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        if !matches!(event.kind, EventKind::CaptionsOpened) {
+            return Err(Error::EventMeta(format!(
+                "event {} is not a captions opened: {:?}",
+                event.id, event.kind
+            )));
+        }
+
+        serde_json::from_value(event.meta.clone()).map_err(|e| {
+            Error::EventMeta(format!(
+                "unable to parse captions opened meta for event {}: {e}",
+                event.id
+            ))
+        })
+    }
+}
+
+/// Average speaking rate used to estimate how long audio takes to listen to
+pub const LISTENING_WORDS_PER_MINUTE: f64 = 150.0;
+/// Average reading rate used to estimate how long captions take to read
+pub const READING_WORDS_PER_MINUTE: f64 = 250.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AudioQuestionCheck {
+    #[serde(rename = "questionId")]
+    pub question_id: ObjectId,
+    /// Estimated from the number of words in the captions.
+    /// Uses the reading rate if captions were opened, otherwise the listening rate.
+    #[serde(rename = "expectedTimeInS")]
+    pub expected_time_in_s: f64,
+    /// Dwell time if the attempt has `QuestionVisit` events, otherwise time since the previous submission
+    #[serde(rename = "timeSpentInS")]
+    pub time_spent_in_s: f64,
+    #[serde(rename = "captionsOpened")]
+    pub captions_opened: bool,
+    #[serde(rename = "isCorrect")]
+    pub is_correct: bool,
+}
+
+impl AudioQuestionCheck {
+    /// Whether the question was answered correctly faster than the audio could have been heard
+    pub fn is_too_fast(&self) -> bool {
+        self.is_correct && self.time_spent_in_s < self.expected_time_in_s
+    }
+}
+
+/// Compares the time spent on each answered audio question with how long its audio takes to listen to.
+///
+/// Audio questions without captions are skipped, as their length cannot be estimated.
+/// `CaptionsOpened` events without a valid question id in `meta` are attributed to the question visited at the time.
+pub fn get_audio_question_checks(attempt: &Attempt, events: &[Event]) -> Vec<AudioQuestionCheck> {
+    let visits = get_question_visits(attempt, events);
+
+    let captions_opened_question_ids: Vec<ObjectId> = events
+        .iter()
+        .filter(|e| matches!(e.kind, EventKind::CaptionsOpened))
+        .filter_map(|e| match CaptionsOpenedMeta::try_from(e) {
+            Ok(CaptionsOpenedMeta { question_id }) => Some(question_id),
+            Err(_) => {
+                let timestamp =
+                    mongodb::bson::DateTime::from_millis(e.timestamp.timestamp_millis());
+                visits
+                    .iter()
+                    .find(|v| v.start_time <= timestamp && timestamp <= v.end_time)
+                    .map(|v| v.question_id)
+            }
+        })
+        .collect();
+
+    let time_to_answers = get_time_to_answer_by_question(attempt);
+
+    let mut checks = vec![];
+    for question in attempt
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .filter(|q| q.submission_time.is_some())
+    {
+        let Some(captions) = question.audio.as_ref().and_then(|a| a.captions.as_ref()) else {
+            continue;
+        };
+        let words = captions.split_whitespace().count() as f64;
+        if words == 0.0 {
+            continue;
+        }
+
+        let captions_opened = captions_opened_question_ids.contains(&question.id);
+        let words_per_minute = if captions_opened {
+            READING_WORDS_PER_MINUTE
+        } else {
+            LISTENING_WORDS_PER_MINUTE
+        };
+        let expected_time_in_s = words / words_per_minute * 60.0;
+
+        let time_spent_in_s = if visits.is_empty() {
+            time_to_answers
+                .iter()
+                .find(|(id, _)| *id == question.id)
+                .map(|(_, t)| *t)
+                .unwrap_or_default()
+        } else {
+            visits
+                .iter()
+                .filter(|v| v.question_id == question.id)
+                .map(|v| v.duration_in_s())
+                .sum()
+        };
+
+        checks.push(AudioQuestionCheck {
+            question_id: question.id,
+            expected_time_in_s,
+            time_spent_in_s,
+            captions_opened,
+            is_correct: compare_answers(&question.answers, &question.generated, &question.selected),
+        });
+    }
+
+    checks
+}
+
+/// Seconds since the previous submission (or the start of the attempt) for each submitted question
+pub(crate) fn get_time_to_answer_by_question(attempt: &Attempt) -> Vec<(ObjectId, f64)> {
+    let mut submissions: Vec<(mongodb::bson::DateTime, ObjectId)> = attempt
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .filter_map(|q| q.submission_time.map(|t| (t, q.id)))
+        .collect();
+    submissions.sort();
+
+    let mut previous_submission_time = attempt.start_time;
+    submissions
+        .into_iter()
+        .map(|(submission_time, id)| {
+            let time_to_answer = submission_time
+                .saturating_duration_since(previous_submission_time)
+                .as_secs_f64();
+            previous_submission_time = submission_time;
+            (id, time_to_answer)
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Default)]
+struct BlurTimes {
+    total_blur_time: f64,
+    total_blur_time_before_last_answer: f64,
+    inferred_intervals: usize,
+}
+
+/// Sums the time out of focus, using `get_blur_intervals`
+fn get_blur_times(attempt: &Attempt, events: &[Event]) -> Result<BlurTimes, Error> {
+    let mut blur_times = BlurTimes::default();
+
+    let Some(last_submission_time) = get_last_submission_time(attempt) else {
+        return Ok(blur_times);
+    };
+
+    for interval in get_blur_intervals(attempt, events) {
+        blur_times.total_blur_time += interval.duration_in_s();
+
+        // Only the part of the interval before the last submission counts
+        let end_before_last_answer = interval.end_time.min(last_submission_time);
+        blur_times.total_blur_time_before_last_answer += end_before_last_answer
+            .saturating_duration_since(interval.start_time)
+            .as_secs_f64();
+
+        if interval.is_inferred() {
+            blur_times.inferred_intervals += 1;
+        }
     }
+
+    let BlurTimes {
+        total_blur_time,
+        total_blur_time_before_last_answer,
+        ..
+    } = blur_times;
+    let total_time = get_total_time(attempt)?;
+
     if total_blur_time > total_time {
         return Err(Error::ModerationScore(format!(
             "total blur time > total time: {total_blur_time} > {total_time}"
@@ -263,61 +1354,282 @@ pub fn get_moderation_score(attempt: &Attempt, events: &Vec<Event>) -> Result<f6
         )));
     }
 
-    let time_weight = ((total_time - total_time_taken) / total_time) * weight;
-    if time_weight > weight {
-        return Err(Error::ModerationScore(format!(
-            "time weight > weight: {time_weight} > {weight}"
-        )));
+    Ok(blur_times)
+}
+
+/// Fraction of the exam time left unused after the last submission.
+pub struct TimeRemainingSignal;
+
+impl ModerationSignal for TimeRemainingSignal {
+    fn name(&self) -> &'static str {
+        "time_remaining"
     }
-    moderation_score += time_weight;
 
-    // Blur time after last submission is worth 1/3 as much as before last submission
-    // Seeing as both total_blur_time_* vars include the time before, it is counted 'twice'
-    let blur_weight = (total_blur_time / total_time) * weight;
-    if blur_weight > weight {
-        return Err(Error::ModerationScore(format!(
-            "blur weight > weight: {blur_weight} > {weight}"
-        )));
+    fn value(&self, attempt: &Attempt, _events: &[Event]) -> Result<f64, Error> {
+        let total_time_taken = get_total_time_taken(attempt)?;
+        let total_time = get_total_time(attempt)?;
+
+        Ok((total_time - total_time_taken) / total_time)
     }
-    moderation_score += blur_weight;
+}
 
-    if total_blur_time_before_last_answer > total_time_taken {
-        return Err(Error::ModerationScore(format!(
-            "total blur time before last answer > total time taken: {total_blur_time_before_last_answer} > {total_time_taken}"
-        )));
+/// Fraction of the exam time spent with the exam out of focus.
+pub struct BlurTimeSignal;
+
+impl ModerationSignal for BlurTimeSignal {
+    fn name(&self) -> &'static str {
+        "blur_time"
     }
 
-    let blur_before_weight = (total_blur_time_before_last_answer / total_time_taken) * weight * 2.0;
-    if blur_before_weight > weight * 2.0 {
-        return Err(Error::ModerationScore(format!(
-            "blur before weight > weight: {blur_before_weight} > {}",
-            weight * 2.0
-        )));
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        let BlurTimes {
+            total_blur_time, ..
+        } = get_blur_times(attempt, events)?;
+        let total_time = get_total_time(attempt)?;
+
+        Ok(total_blur_time / total_time)
     }
-    moderation_score += blur_before_weight;
+}
 
-    if moderation_score > 1.0 {
-        tracing::error!(
-            attempt = %attempt.id,
-            moderation_score,
-            "moderation score should never be > 1.0"
-        );
+/// Fraction of the time taken to answer all questions spent with the exam out of focus.
+pub struct BlurBeforeLastAnswerSignal;
+
+impl ModerationSignal for BlurBeforeLastAnswerSignal {
+    fn name(&self) -> &'static str {
+        "blur_before_last_answer"
     }
 
-    Ok(moderation_score)
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        let BlurTimes {
+            total_blur_time_before_last_answer,
+            ..
+        } = get_blur_times(attempt, events)?;
+        let total_time_taken = get_total_time_taken(attempt)?;
+
+        if total_blur_time_before_last_answer > total_time_taken {
+            return Err(Error::ModerationScore(format!(
+                "total blur time before last answer > total time taken: {total_blur_time_before_last_answer} > {total_time_taken}"
+            )));
+        }
+        if total_time_taken == 0.0 {
+            return Ok(0.0);
+        }
+
+        Ok(total_blur_time_before_last_answer / total_time_taken)
+    }
+}
+
+/// Approaches 1.0 as the exam is exited and re-entered more often: `1 - 1 / sessions`.
+pub struct SessionCountSignal;
+
+impl ModerationSignal for SessionCountSignal {
+    fn name(&self) -> &'static str {
+        "session_count"
+    }
+
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        let AttemptSessions { sessions, .. } = get_attempt_sessions(attempt, events);
+        if sessions.is_empty() {
+            return Ok(0.0);
+        }
+
+        Ok(1.0 - 1.0 / sessions.len() as f64)
+    }
+}
+
+/// Fraction of the exam time spent outside of the exam, between exiting and re-entering.
+pub struct TimeOutsideSignal;
+
+impl ModerationSignal for TimeOutsideSignal {
+    fn name(&self) -> &'static str {
+        "time_outside"
+    }
+
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        let AttemptSessions {
+            time_outside_in_s, ..
+        } = get_attempt_sessions(attempt, events);
+        let total_time = get_total_time(attempt)?;
+
+        Ok((time_outside_in_s / total_time).min(1.0))
+    }
+}
+
+/// Fraction of answered questions visited for less than `min_dwell_time_in_s`.
+///
+/// Attempts without any `QuestionVisit` events have a value of 0.0.
+pub struct LowDwellTimeSignal {
+    pub min_dwell_time_in_s: f64,
+}
+
+impl Default for LowDwellTimeSignal {
+    fn default() -> Self {
+        Self {
+            min_dwell_time_in_s: 3.0,
+        }
+    }
+}
+
+impl ModerationSignal for LowDwellTimeSignal {
+    fn name(&self) -> &'static str {
+        "low_dwell_time"
+    }
+
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        let visits = get_question_visits(attempt, events);
+        if visits.is_empty() {
+            return Ok(0.0);
+        }
+
+        let mut answered = 0;
+        let mut low_dwell_time = 0;
+        for question in attempt
+            .question_sets
+            .iter()
+            .flat_map(|qs| qs.questions.iter())
+            .filter(|q| q.submission_time.is_some())
+        {
+            answered += 1;
+            let dwell_time_in_s: f64 = visits
+                .iter()
+                .filter(|v| v.question_id == question.id)
+                .map(|v| v.duration_in_s())
+                .sum();
+            if dwell_time_in_s < self.min_dwell_time_in_s {
+                low_dwell_time += 1;
+            }
+        }
+
+        if answered == 0 {
+            return Ok(0.0);
+        }
+
+        Ok(low_dwell_time as f64 / answered as f64)
+    }
+}
+
+/// Fraction of correctly answered audio questions answered faster than their audio could be heard.
+/// See `get_audio_question_checks`.
+pub struct AudioListeningSignal;
+
+impl ModerationSignal for AudioListeningSignal {
+    fn name(&self) -> &'static str {
+        "audio_listening"
+    }
+
+    fn value(&self, attempt: &Attempt, events: &[Event]) -> Result<f64, Error> {
+        let checks: Vec<AudioQuestionCheck> = get_audio_question_checks(attempt, events)
+            .into_iter()
+            .filter(|c| c.is_correct)
+            .collect();
+        if checks.is_empty() {
+            return Ok(0.0);
+        }
+
+        let too_fast = checks.iter().filter(|c| c.is_too_fast()).count();
+        Ok(too_fast as f64 / checks.len() as f64)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::f64;
+    use std::{f64, time::Duration};
 
-    use bson::oid::ObjectId;
+    use bson::{DateTime, oid::ObjectId};
     use prisma::{
-        ExamEnvironmentExam, ExamEnvironmentExamAttempt, ExamEnvironmentGeneratedExam,
-        supabase::Event,
+        ExamEnvironmentAnswer, ExamEnvironmentConfig, ExamEnvironmentExam,
+        ExamEnvironmentExamAttempt, ExamEnvironmentGeneratedExam,
+        ExamEnvironmentGeneratedMultipleChoiceQuestion, ExamEnvironmentGeneratedQuestionSet,
+        ExamEnvironmentMultipleChoiceQuestion, ExamEnvironmentMultipleChoiceQuestionAttempt,
+        ExamEnvironmentQuestionSet, ExamEnvironmentQuestionSetAttempt, supabase::Event,
+    };
+
+    use crate::{
+        attempt::{
+            Attempt, AttemptQuestionSet, AttemptQuestionSetQuestion, BlurIntervalEnd,
+            IntegrityIssue, ModerationScorer, ModerationSignal, add_question_visits,
+            construct_attempt, get_attempt_sessions, get_attempt_stats, get_audio_question_checks,
+            get_blur_intervals, get_moderation_report, get_moderation_score,
+            get_submission_gap_stats, get_time_between_submissions, verify_attempt,
+        },
+        error::Error,
     };
 
-    use crate::attempt::{construct_attempt, get_moderation_score};
+    fn event(kind: &str, start_time: DateTime, after_s: i64) -> Event {
+        let timestamp = DateTime::from_millis(start_time.timestamp_millis() + after_s * 1000);
+        serde_json::from_value(serde_json::json!({
+            "id": ObjectId::new().to_hex(),
+            "timestamp": timestamp.try_to_rfc3339_string().unwrap(),
+            "kind": kind,
+            "meta": null,
+            "attempt_id": ObjectId::new().to_hex(),
+        }))
+        .unwrap()
+    }
+
+    /// Creates a question with one correct and one incorrect generated answer
+    fn attempt_question(
+        start_time: DateTime,
+        submitted_after_s: Option<i64>,
+        correct: bool,
+    ) -> AttemptQuestionSetQuestion {
+        let correct_answer = ExamEnvironmentAnswer {
+            id: ObjectId::new(),
+            is_correct: true,
+            text: "correct".to_string(),
+        };
+        let incorrect_answer = ExamEnvironmentAnswer {
+            id: ObjectId::new(),
+            is_correct: false,
+            text: "incorrect".to_string(),
+        };
+        let selected = match submitted_after_s {
+            Some(_) if correct => vec![correct_answer.id],
+            Some(_) => vec![incorrect_answer.id],
+            None => vec![],
+        };
+
+        AttemptQuestionSetQuestion {
+            id: ObjectId::new(),
+            text: "question".to_string(),
+            tags: vec![],
+            deprecated: false,
+            audio: None,
+            generated: vec![correct_answer.id, incorrect_answer.id],
+            answers: vec![correct_answer, incorrect_answer],
+            selected,
+            submission_time: submitted_after_s
+                .map(|s| DateTime::from_millis(start_time.timestamp_millis() + s * 1000)),
+            dwell_time_in_s: 0.0,
+            visit_order: vec![],
+        }
+    }
+
+    fn attempt_with_questions(
+        start_time: DateTime,
+        total_time_in_s: i64,
+        questions: Vec<AttemptQuestionSetQuestion>,
+    ) -> Attempt {
+        Attempt {
+            id: ObjectId::new(),
+            exam_id: ObjectId::new(),
+            generated_exam_id: ObjectId::new(),
+            user_id: ObjectId::new(),
+            prerequisites: vec![],
+            deprecated: false,
+            question_sets: vec![AttemptQuestionSet {
+                id: ObjectId::new(),
+                _type: Default::default(),
+                context: None,
+                questions,
+            }],
+            config: ExamEnvironmentConfig {
+                total_time_in_s,
+                ..Default::default()
+            },
+            start_time,
+        }
+    }
 
     fn get_events_for_attempt(attempt_id: &ObjectId) -> Vec<Event> {
         let event = std::fs::read(format!("../fixtures/events/{}", attempt_id.to_hex())).unwrap();
@@ -387,4 +1699,498 @@ mod tests {
         assert!(max <= 1.0);
         assert!(min >= 0.0);
     }
+
+    #[test]
+    fn attempt_stats() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let attempt = attempt_with_questions(
+            start_time,
+            600,
+            vec![
+                attempt_question(start_time, Some(30), true),
+                attempt_question(start_time, Some(10), false),
+                attempt_question(start_time, None, false),
+            ],
+        );
+
+        let stats = get_attempt_stats(attempt);
+
+        assert_eq!(stats.total_questions, 3);
+        assert_eq!(stats.answered, 2);
+        assert_eq!(stats.correct, 1);
+        assert_eq!(stats.time_to_complete, 30.0);
+        assert_eq!(stats.average_time_per_question, 15.0);
+
+        let series: Vec<(usize, f64, bool)> = stats
+            .time_to_answers
+            .iter()
+            .map(|t| (t.name, t.value, t.is_correct))
+            .collect();
+        assert_eq!(series, vec![(1, 10.0, false), (2, 20.0, true)]);
+    }
+
+    #[test]
+    fn submission_gap_stats() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        // Out of order to check sorting, with a burst of 4 submissions 200ms apart
+        let offsets_in_ms = [60_000, 1_000, 60_400, 60_200, 5_000, 60_600, 120_000];
+        let attempt = ExamEnvironmentExamAttempt {
+            start_time,
+            question_sets: vec![ExamEnvironmentQuestionSetAttempt {
+                id: ObjectId::new(),
+                questions: offsets_in_ms
+                    .iter()
+                    .map(|ms| ExamEnvironmentMultipleChoiceQuestionAttempt {
+                        id: ObjectId::new(),
+                        answers: vec![ObjectId::new()],
+                        submission_time: DateTime::from_millis(start_time.timestamp_millis() + ms),
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        };
+
+        let gaps = get_time_between_submissions(attempt);
+        assert_eq!(
+            gaps,
+            [4_000, 55_000, 200, 200, 200, 59_400].map(Duration::from_millis)
+        );
+
+        let stats = get_submission_gap_stats(&gaps);
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.min_in_s, 0.2);
+        assert_eq!(stats.median_in_s, 0.2);
+        assert_eq!(stats.p95_in_s, 59.4);
+
+        let longest_gap = stats.longest_gap.unwrap();
+        assert_eq!(longest_gap.index, 6);
+        assert_eq!(longest_gap.duration_in_s, 59.4);
+
+        assert_eq!(stats.bursts.len(), 1);
+        assert_eq!(stats.bursts[0].start, 2);
+        assert_eq!(stats.bursts[0].submissions, 4);
+        assert!((stats.bursts[0].duration_in_s - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn default_moderation_score() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let attempt = attempt_with_questions(
+            start_time,
+            1000,
+            vec![
+                attempt_question(start_time, Some(200), true),
+                attempt_question(start_time, Some(500), true),
+            ],
+        );
+        let events = vec![
+            event("BLUR", start_time, 100),
+            event("FOCUS", start_time, 200),
+        ];
+
+        let score = get_moderation_score(&attempt, &events).unwrap();
+
+        // time remaining: 0.5 * 0.125, blur: 0.1 * 0.125, blur before last answer: 0.2 * 0.25
+        assert!((score - 0.125).abs() < 1e-9);
+    }
+
+    struct ConstantSignal(f64);
+
+    impl ModerationSignal for ConstantSignal {
+        fn name(&self) -> &'static str {
+            "constant"
+        }
+
+        fn value(&self, _attempt: &Attempt, _events: &[Event]) -> Result<f64, Error> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn moderation_scorer_weights() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let attempt = attempt_with_questions(
+            start_time,
+            1000,
+            vec![attempt_question(start_time, Some(200), true)],
+        );
+
+        let scorer = ModerationScorer::new()
+            .with_signal(ConstantSignal(1.0), 1.0)
+            .with_signal(ConstantSignal(0.0), 3.0);
+        assert_eq!(scorer.score(&attempt, &[]).unwrap(), 0.25);
+
+        let scorer = ModerationScorer::new().with_signal(ConstantSignal(1.5), 1.0);
+        assert!(scorer.score(&attempt, &[]).is_err());
+
+        let scorer = ModerationScorer::new().with_signal(ConstantSignal(1.0), 0.0);
+        assert!(scorer.score(&attempt, &[]).is_err());
+    }
+
+    #[test]
+    fn moderation_report() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let attempt = attempt_with_questions(
+            start_time,
+            1000,
+            vec![attempt_question(start_time, Some(500), true)],
+        );
+        let events = vec![
+            event("BLUR", start_time, 100),
+            event("FOCUS", start_time, 200),
+            event("BLUR", start_time, 600),
+            event("FOCUS", start_time, 650),
+        ];
+
+        let report = get_moderation_report(&attempt, &events).unwrap();
+
+        assert_eq!(report.total_blur_time_in_s, 150.0);
+        assert_eq!(report.total_blur_time_before_last_answer_in_s, 100.0);
+        assert_eq!(report.total_time_taken_in_s, 500.0);
+        assert_eq!(report.total_time_in_s, 1000.0);
+
+        let components: Vec<(&str, f64)> = report
+            .components
+            .iter()
+            .map(|c| (c.name.as_str(), c.weight))
+            .collect();
+        assert_eq!(
+            components,
+            vec![
+                ("time_remaining", 0.125),
+                ("blur_time", 0.125),
+                ("blur_before_last_answer", 0.25),
+                ("session_count", 0.15),
+                ("time_outside", 0.15),
+                ("low_dwell_time", 0.1),
+                ("audio_listening", 0.1)
+            ]
+        );
+
+        let total: f64 = report.components.iter().map(|c| c.contribution).sum();
+        assert_eq!(report.score, total);
+        assert_eq!(
+            report.score,
+            get_moderation_score(&attempt, &events).unwrap()
+        );
+        assert!(report.to_string().starts_with("Moderation score: "));
+    }
+
+    #[test]
+    fn blur_intervals_without_focus() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let attempt = attempt_with_questions(
+            start_time,
+            1000,
+            vec![attempt_question(start_time, Some(500), true)],
+        );
+        let events = vec![
+            // Consecutive blurs extend the first
+            event("BLUR", start_time, 10),
+            event("BLUR", start_time, 20),
+            event("FOCUS", start_time, 30),
+            event("BLUR", start_time, 100),
+            event("EXAM_EXIT", start_time, 150),
+            // Trailing blur before the last submission
+            event("BLUR", start_time, 400),
+        ];
+
+        let intervals = get_blur_intervals(&attempt, &events);
+        let summary: Vec<(f64, BlurIntervalEnd)> = intervals
+            .iter()
+            .map(|i| (i.duration_in_s(), i.end.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (20.0, BlurIntervalEnd::Focus),
+                (50.0, BlurIntervalEnd::ExamExit),
+                (100.0, BlurIntervalEnd::LastSubmission),
+            ]
+        );
+
+        // Trailing blur after the last submission is closed at the end of the exam
+        let events = vec![event("BLUR", start_time, 900)];
+        let intervals = get_blur_intervals(&attempt, &events);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].duration_in_s(), 100.0);
+        assert_eq!(intervals[0].end, BlurIntervalEnd::ExamEnd);
+
+        let report = get_moderation_report(&attempt, &events).unwrap();
+        assert_eq!(report.total_blur_time_in_s, 100.0);
+        assert_eq!(report.total_blur_time_before_last_answer_in_s, 0.0);
+        assert_eq!(report.inferred_blur_intervals, 1);
+    }
+
+    #[test]
+    fn attempt_sessions() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let attempt = attempt_with_questions(
+            start_time,
+            1000,
+            vec![
+                attempt_question(start_time, Some(100), true),
+                attempt_question(start_time, Some(400), true),
+            ],
+        );
+        let events = vec![
+            event("EXAM_EXIT", start_time, 150),
+            event("EXAM_EXIT", start_time, 160),
+            // Re-entered on next submission
+            event("EXAM_EXIT", start_time, 500),
+            event("FOCUS", start_time, 800),
+            event("EXAM_EXIT", start_time, 900),
+        ];
+
+        let sessions = get_attempt_sessions(&attempt, &events);
+
+        let summary: Vec<(i64, i64, bool)> = sessions
+            .sessions
+            .iter()
+            .map(|s| {
+                (
+                    (s.start_time.timestamp_millis() - start_time.timestamp_millis()) / 1000,
+                    (s.end_time.timestamp_millis() - start_time.timestamp_millis()) / 1000,
+                    s.exited,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(0, 150, true), (400, 500, true), (800, 900, true)]
+        );
+        assert_eq!(sessions.time_outside_in_s, 250.0 + 300.0);
+
+        let report = get_moderation_report(&attempt, &events).unwrap();
+        assert_eq!(report.sessions, 3);
+        assert_eq!(report.time_outside_in_s, 550.0);
+        let session_count = report
+            .components
+            .iter()
+            .find(|c| c.name == "session_count")
+            .unwrap();
+        assert!((session_count.value - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    fn question_visit(question_id: &ObjectId, start_time: DateTime, after_s: i64) -> Event {
+        let mut event = event("QUESTION_VISIT", start_time, after_s);
+        event.meta = serde_json::json!({ "questionId": question_id.to_hex() });
+        event
+    }
+
+    #[test]
+    fn question_visits() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let mut attempt = attempt_with_questions(
+            start_time,
+            1000,
+            vec![
+                attempt_question(start_time, Some(100), true),
+                attempt_question(start_time, Some(102), true),
+                attempt_question(start_time, None, false),
+            ],
+        );
+        let ids: Vec<ObjectId> = attempt.question_sets[0]
+            .questions
+            .iter()
+            .map(|q| q.id)
+            .collect();
+        let events = vec![
+            question_visit(&ids[0], start_time, 10),
+            question_visit(&ids[1], start_time, 101),
+            question_visit(&ids[0], start_time, 102),
+            event("EXAM_EXIT", start_time, 150),
+            question_visit(&ids[2], start_time, 200),
+            event("FOCUS", start_time, 260),
+        ];
+
+        add_question_visits(&mut attempt, &events);
+
+        let questions = &attempt.question_sets[0].questions;
+        assert_eq!(questions[0].dwell_time_in_s, 91.0 + 48.0);
+        assert_eq!(questions[0].visit_order, vec![0, 2]);
+        assert_eq!(questions[0].visit_count(), 2);
+        assert_eq!(questions[1].dwell_time_in_s, 1.0);
+        assert_eq!(questions[1].visit_order, vec![1]);
+        assert_eq!(questions[2].dwell_time_in_s, 60.0);
+
+        let report = get_moderation_report(&attempt, &events).unwrap();
+        let low_dwell_time = report
+            .components
+            .iter()
+            .find(|c| c.name == "low_dwell_time")
+            .unwrap();
+        assert_eq!(low_dwell_time.value, 0.5);
+    }
+
+    #[test]
+    fn audio_question_checks() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        // 150 words -> 60s to listen to, 36s to read
+        let captions = vec!["word"; 150].join(" ");
+        let mut questions = vec![
+            attempt_question(start_time, Some(40), true),
+            attempt_question(start_time, Some(70), true),
+            attempt_question(start_time, Some(100), false),
+            attempt_question(start_time, Some(200), true),
+        ];
+        for question in questions.iter_mut().take(3) {
+            question.audio = Some(prisma::ExamEnvironmentAudio {
+                captions: Some(captions.clone()),
+                url: "https://example.com/audio.mp3".to_string(),
+            });
+        }
+        let ids: Vec<ObjectId> = questions.iter().map(|q| q.id).collect();
+        let attempt = attempt_with_questions(start_time, 1000, questions);
+        let mut captions_opened = event("CAPTIONS_OPENED", start_time, 35);
+        captions_opened.meta = serde_json::json!({ "questionId": ids[1].to_hex() });
+
+        let checks = get_audio_question_checks(&attempt, &[captions_opened.clone()]);
+
+        let summary: Vec<(f64, f64, bool, bool)> = checks
+            .iter()
+            .map(|c| {
+                (
+                    c.expected_time_in_s,
+                    c.time_spent_in_s,
+                    c.captions_opened,
+                    c.is_too_fast(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (60.0, 40.0, false, true),
+                (36.0, 30.0, true, true),
+                // Incorrect answers are never too fast
+                (60.0, 30.0, false, false),
+            ]
+        );
+
+        let report = get_moderation_report(&attempt, &[captions_opened]).unwrap();
+        let audio_listening = report
+            .components
+            .iter()
+            .find(|c| c.name == "audio_listening")
+            .unwrap();
+        assert_eq!(audio_listening.value, 1.0);
+    }
+
+    #[test]
+    fn verify_attempt_issues() {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let after = |s: i64| DateTime::from_millis(start_time.timestamp_millis() + s * 1000);
+        // 3 questions with 1 correct and 2 incorrect answers, each generated without the last incorrect answer
+        let questions: Vec<ExamEnvironmentMultipleChoiceQuestion> = (0..3)
+            .map(|_| ExamEnvironmentMultipleChoiceQuestion {
+                id: ObjectId::new(),
+                answers: [true, false, false]
+                    .iter()
+                    .map(|is_correct| ExamEnvironmentAnswer {
+                        id: ObjectId::new(),
+                        is_correct: *is_correct,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        let answer = |q: usize, a: usize| questions[q].answers[a].id;
+        let exam = ExamEnvironmentExam {
+            id: ObjectId::new(),
+            question_sets: vec![ExamEnvironmentQuestionSet {
+                id: ObjectId::new(),
+                questions: questions.clone(),
+                ..Default::default()
+            }],
+            config: ExamEnvironmentConfig {
+                total_time_in_s: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let question_set_id = exam.question_sets[0].id;
+        let generation = ExamEnvironmentGeneratedExam {
+            id: ObjectId::new(),
+            exam_id: exam.id,
+            question_sets: vec![ExamEnvironmentGeneratedQuestionSet {
+                id: question_set_id,
+                questions: questions[..2]
+                    .iter()
+                    .map(|q| ExamEnvironmentGeneratedMultipleChoiceQuestion {
+                        id: q.id,
+                        answers: q.answers[..2].iter().map(|a| a.id).collect(),
+                    })
+                    .collect(),
+            }],
+            deprecated: false,
+            version: 1,
+        };
+        let attempt_question = |id: ObjectId, answers: Vec<ObjectId>, after_s: i64| {
+            ExamEnvironmentMultipleChoiceQuestionAttempt {
+                id,
+                answers,
+                submission_time: after(after_s),
+            }
+        };
+        let mut attempt = ExamEnvironmentExamAttempt {
+            id: ObjectId::new(),
+            exam_id: exam.id,
+            generated_exam_id: generation.id,
+            question_sets: vec![ExamEnvironmentQuestionSetAttempt {
+                id: question_set_id,
+                questions: vec![
+                    attempt_question(questions[0].id, vec![answer(0, 0)], 10),
+                    attempt_question(questions[1].id, vec![answer(1, 1)], 90),
+                ],
+            }],
+            start_time,
+            ..Default::default()
+        };
+
+        assert!(verify_attempt(&exam, &generation, &attempt).is_empty());
+
+        let unknown_question_set_id = ObjectId::new();
+        attempt.question_sets[0].questions = vec![
+            attempt_question(questions[0].id, vec![answer(0, 0), answer(0, 1)], -1),
+            attempt_question(questions[1].id, vec![answer(1, 2)], 101),
+            attempt_question(questions[2].id, vec![answer(2, 0)], 20),
+        ];
+        attempt
+            .question_sets
+            .push(ExamEnvironmentQuestionSetAttempt {
+                id: unknown_question_set_id,
+                questions: vec![attempt_question(ObjectId::new(), vec![], 30)],
+            });
+
+        assert_eq!(
+            verify_attempt(&exam, &generation, &attempt),
+            vec![
+                IntegrityIssue::MultipleAnswers {
+                    question_id: questions[0].id,
+                    number_of_answers: 2,
+                },
+                IntegrityIssue::SubmissionBeforeStart {
+                    question_id: questions[0].id,
+                    submission_time: after(-1),
+                },
+                IntegrityIssue::AnswerOutsideGeneration {
+                    question_id: questions[1].id,
+                    answer_id: answer(1, 2),
+                },
+                IntegrityIssue::SubmissionAfterEnd {
+                    question_id: questions[1].id,
+                    submission_time: after(101),
+                },
+                IntegrityIssue::QuestionOutsideGeneration {
+                    question_set_id,
+                    question_id: questions[2].id,
+                },
+                IntegrityIssue::UnknownQuestionSet {
+                    question_set_id: unknown_question_set_id,
+                },
+            ]
+        );
+    }
 }
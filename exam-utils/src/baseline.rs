@@ -0,0 +1,289 @@
+//! Population timing baselines
+//!
+//! Per-question answer times from historical attempts are summarised as the mean and standard deviation
+//! of their natural log, as answer times are heavily right-skewed.
+//! Attempts answering far below the population distribution are flagged by `SpeedAnomalySignal`.
+use mongodb::bson::oid::ObjectId;
+use prisma::supabase::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attempt::{Attempt, ModerationSignal, get_time_to_answer_by_question},
+    error::Error,
+    misc::compare_answers,
+};
+
+/// Answer times are floored to this, to avoid `ln(0)`
+const MIN_TIME_TO_ANSWER_IN_S: f64 = 0.5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExamTimingBaseline {
+    #[serde(rename = "examId")]
+    pub exam_id: ObjectId,
+    /// Number of attempts the baseline was built from
+    #[serde(rename = "numAttempts")]
+    pub num_attempts: usize,
+    pub questions: Vec<QuestionTimingBaseline>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionTimingBaseline {
+    #[serde(rename = "questionId")]
+    pub question_id: ObjectId,
+    /// Number of submissions of the question
+    pub count: usize,
+    /// Mean of `ln(time to answer in seconds)`
+    #[serde(rename = "meanLogTime")]
+    pub mean_log_time: f64,
+    /// Sample standard deviation of `ln(time to answer in seconds)`
+    #[serde(rename = "stdLogTime")]
+    pub std_log_time: f64,
+}
+
+impl QuestionTimingBaseline {
+    /// Number of standard deviations the time is from the mean, in log space.
+    ///
+    /// Returns `None` if the baseline has no spread.
+    pub fn z_score(&self, time_to_answer_in_s: f64) -> Option<f64> {
+        if self.std_log_time <= 0.0 {
+            return None;
+        }
+        let log_time = time_to_answer_in_s.max(MIN_TIME_TO_ANSWER_IN_S).ln();
+        Some((log_time - self.mean_log_time) / self.std_log_time)
+    }
+}
+
+/// Builds per-question timing baselines from attempts of the same exam.
+///
+/// Time to answer is the time since the previous submission, or the start of the attempt.
+/// Attempts of other exams are ignored. Questions are ordered by id.
+pub fn build_timing_baseline(exam_id: ObjectId, attempts: &[Attempt]) -> ExamTimingBaseline {
+    let mut times: Vec<(ObjectId, f64)> = vec![];
+    let mut num_attempts = 0;
+    for attempt in attempts.iter().filter(|a| a.exam_id == exam_id) {
+        num_attempts += 1;
+        times.extend(
+            get_time_to_answer_by_question(attempt)
+                .into_iter()
+                .map(|(id, t)| (id, t.max(MIN_TIME_TO_ANSWER_IN_S).ln())),
+        );
+    }
+    times.sort_by_key(|(id, _)| *id);
+
+    let questions = times
+        .chunk_by(|a, b| a.0 == b.0)
+        .map(|chunk| {
+            let count = chunk.len();
+            let mean_log_time = chunk.iter().map(|(_, t)| t).sum::<f64>() / count as f64;
+            let std_log_time = if count > 1 {
+                let variance = chunk
+                    .iter()
+                    .map(|(_, t)| (t - mean_log_time).powi(2))
+                    .sum::<f64>()
+                    / (count - 1) as f64;
+                variance.sqrt()
+            } else {
+                0.0
+            };
+
+            QuestionTimingBaseline {
+                question_id: chunk[0].0,
+                count,
+                mean_log_time,
+                std_log_time,
+            }
+        })
+        .collect();
+
+    ExamTimingBaseline {
+        exam_id,
+        num_attempts,
+        questions,
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpeedAnomaly {
+    #[serde(rename = "questionId")]
+    pub question_id: ObjectId,
+    #[serde(rename = "timeToAnswerInS")]
+    pub time_to_answer_in_s: f64,
+    #[serde(rename = "zScore")]
+    pub z_score: f64,
+    #[serde(rename = "isCorrect")]
+    pub is_correct: bool,
+}
+
+/// Fraction of correctly answered questions answered anomalously fast compared to the population.
+///
+/// Questions with fewer than `min_samples` submissions in the baseline are ignored.
+/// A question is anomalous if its time to answer is more than `max_z_score` standard deviations below the mean.
+pub struct SpeedAnomalySignal {
+    pub baseline: ExamTimingBaseline,
+    pub min_samples: usize,
+    pub max_z_score: f64,
+}
+
+impl SpeedAnomalySignal {
+    pub fn new(baseline: ExamTimingBaseline) -> Self {
+        Self {
+            baseline,
+            min_samples: 30,
+            max_z_score: 2.0,
+        }
+    }
+
+    /// Answered questions of the attempt, with their z-score against the baseline
+    pub fn get_speed_anomalies(&self, attempt: &Attempt) -> Vec<SpeedAnomaly> {
+        let times = get_time_to_answer_by_question(attempt);
+
+        attempt
+            .question_sets
+            .iter()
+            .flat_map(|qs| qs.questions.iter())
+            .filter_map(|q| {
+                let (_, time_to_answer_in_s) = times.iter().find(|(id, _)| *id == q.id)?;
+                let baseline = self
+                    .baseline
+                    .questions
+                    .iter()
+                    .find(|b| b.question_id == q.id && b.count >= self.min_samples)?;
+                let z_score = baseline.z_score(*time_to_answer_in_s)?;
+
+                Some(SpeedAnomaly {
+                    question_id: q.id,
+                    time_to_answer_in_s: *time_to_answer_in_s,
+                    z_score,
+                    is_correct: compare_answers(&q.answers, &q.generated, &q.selected),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ModerationSignal for SpeedAnomalySignal {
+    fn name(&self) -> &'static str {
+        "speed_anomaly"
+    }
+
+    fn value(&self, attempt: &Attempt, _events: &[Event]) -> Result<f64, Error> {
+        if attempt.exam_id != self.baseline.exam_id {
+            return Err(Error::ModerationScore(format!(
+                "timing baseline for exam {} used for attempt of exam {}",
+                self.baseline.exam_id, attempt.exam_id
+            )));
+        }
+
+        let correct: Vec<SpeedAnomaly> = self
+            .get_speed_anomalies(attempt)
+            .into_iter()
+            .filter(|a| a.is_correct)
+            .collect();
+        if correct.is_empty() {
+            return Ok(0.0);
+        }
+
+        let anomalous = correct
+            .iter()
+            .filter(|a| a.z_score < -self.max_z_score)
+            .count();
+        Ok(anomalous as f64 / correct.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{DateTime, oid::ObjectId};
+    use prisma::{ExamEnvironmentAnswer, ExamEnvironmentConfig};
+
+    use crate::attempt::{
+        Attempt, AttemptQuestionSet, AttemptQuestionSetQuestion, ModerationSignal,
+    };
+
+    use super::{SpeedAnomalySignal, build_timing_baseline};
+
+    /// Attempt answering each question correctly, taking the given number of seconds per question
+    fn attempt(exam_id: ObjectId, question_ids: &[ObjectId], times_in_s: &[i64]) -> Attempt {
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let mut elapsed_s = 0;
+        let questions = question_ids
+            .iter()
+            .zip(times_in_s)
+            .map(|(id, t)| {
+                elapsed_s += t;
+                let answer = ExamEnvironmentAnswer {
+                    id: ObjectId::new(),
+                    is_correct: true,
+                    text: "correct".to_string(),
+                };
+                AttemptQuestionSetQuestion {
+                    id: *id,
+                    text: "question".to_string(),
+                    tags: vec![],
+                    deprecated: false,
+                    audio: None,
+                    selected: vec![answer.id],
+                    generated: vec![answer.id],
+                    answers: vec![answer],
+                    submission_time: Some(DateTime::from_millis(
+                        start_time.timestamp_millis() + elapsed_s * 1000,
+                    )),
+                    dwell_time_in_s: 0.0,
+                    visit_order: vec![],
+                }
+            })
+            .collect();
+
+        Attempt {
+            id: ObjectId::new(),
+            exam_id,
+            generated_exam_id: ObjectId::new(),
+            user_id: ObjectId::new(),
+            prerequisites: vec![],
+            deprecated: false,
+            question_sets: vec![AttemptQuestionSet {
+                id: ObjectId::new(),
+                _type: Default::default(),
+                context: None,
+                questions,
+            }],
+            config: ExamEnvironmentConfig::default(),
+            start_time,
+        }
+    }
+
+    #[test]
+    fn timing_baseline() {
+        let exam_id = ObjectId::new();
+        let question_ids = [ObjectId::new(), ObjectId::new()];
+        let mut attempts: Vec<Attempt> = (0..40)
+            .map(|i| attempt(exam_id, &question_ids, &[20 + i % 5, 40 + i % 10]))
+            .collect();
+        // Attempts of other exams are ignored
+        attempts.push(attempt(ObjectId::new(), &question_ids, &[1, 1]));
+
+        let baseline = build_timing_baseline(exam_id, &attempts);
+        assert_eq!(baseline.num_attempts, 40);
+        assert_eq!(baseline.questions.len(), 2);
+        for question in baseline.questions.iter() {
+            assert_eq!(question.count, 40);
+            assert!(question.std_log_time > 0.0);
+        }
+        let first = baseline
+            .questions
+            .iter()
+            .find(|q| q.question_id == question_ids[0])
+            .unwrap();
+        let expected_mean = (0..40).map(|i| ((20 + i % 5) as f64).ln()).sum::<f64>() / 40.0;
+        assert!((first.mean_log_time - expected_mean).abs() < 1e-9);
+
+        let signal = SpeedAnomalySignal::new(baseline);
+        let typical = attempt(exam_id, &question_ids, &[22, 45]);
+        assert_eq!(signal.value(&typical, &[]).unwrap(), 0.0);
+        let fast = attempt(exam_id, &question_ids, &[2, 45]);
+        assert_eq!(signal.value(&fast, &[]).unwrap(), 0.5);
+
+        let other_exam = attempt(ObjectId::new(), &question_ids, &[2, 2]);
+        assert!(signal.value(&other_exam, &[]).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+//! Cross-attempt collusion detection
+//!
+//! Attempts of the same exam are compared pairwise on:
+//! - identical wrong answers to the same questions
+//! - how close together the same questions were submitted
+//! - the order the shared questions were submitted in
+//!
+//! Suspicious pairs are grouped into clusters of attempts for moderators to review.
+use std::collections::BTreeMap;
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    attempt::{Attempt, AttemptQuestionSetQuestion},
+    misc::compare_answers,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollusionConfig {
+    /// Attempts started further apart than this are not compared
+    #[serde(rename = "maxStartTimeDifferenceInS")]
+    pub max_start_time_difference_in_s: f64,
+    /// Submissions of the same question within this many seconds of each other count as simultaneous
+    #[serde(rename = "maxSubmissionOffsetInS")]
+    pub max_submission_offset_in_s: f64,
+    /// Pairs sharing fewer submitted questions than this are not scored
+    #[serde(rename = "minSharedQuestions")]
+    pub min_shared_questions: usize,
+    /// Pairs need at least this many identical wrong answers to be suspicious
+    #[serde(rename = "minIdenticalWrongAnswers")]
+    pub min_identical_wrong_answers: usize,
+    /// Pairs need at least this score to be suspicious
+    #[serde(rename = "minScore")]
+    pub min_score: f64,
+}
+
+impl Default for CollusionConfig {
+    fn default() -> Self {
+        Self {
+            max_start_time_difference_in_s: 24.0 * 60.0 * 60.0,
+            max_submission_offset_in_s: 10.0,
+            min_shared_questions: 5,
+            min_identical_wrong_answers: 2,
+            min_score: 0.5,
+        }
+    }
+}
+
+/// Weight of each similarity in `AttemptComparison::score`.
+/// Ordering is weighted lowest, as attempts of the same generation see questions in the same order.
+const WRONG_ANSWER_WEIGHT: f64 = 0.5;
+const TIMELINE_WEIGHT: f64 = 0.3;
+const ORDER_WEIGHT: f64 = 0.2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttemptComparison {
+    #[serde(rename = "attemptIds")]
+    pub attempt_ids: [ObjectId; 2],
+    #[serde(rename = "userIds")]
+    pub user_ids: [ObjectId; 2],
+    /// Number of questions submitted in both attempts
+    #[serde(rename = "sharedQuestions")]
+    pub shared_questions: usize,
+    /// Number of shared questions answered incorrectly with the same answers in both attempts
+    #[serde(rename = "identicalWrongAnswers")]
+    pub identical_wrong_answers: usize,
+    /// Identical wrong answers over shared questions answered incorrectly in either attempt
+    #[serde(rename = "wrongAnswerSimilarity")]
+    pub wrong_answer_similarity: f64,
+    /// Fraction of shared questions submitted within `max_submission_offset_in_s` of each other
+    #[serde(rename = "timelineSimilarity")]
+    pub timeline_similarity: f64,
+    /// Fraction of pairs of shared questions submitted in the same relative order
+    #[serde(rename = "orderSimilarity")]
+    pub order_similarity: f64,
+    pub score: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollusionCluster {
+    #[serde(rename = "examId")]
+    pub exam_id: ObjectId,
+    /// Sorted attempt ids
+    #[serde(rename = "attemptIds")]
+    pub attempt_ids: Vec<ObjectId>,
+    /// Suspicious pairs linking the attempts in the cluster
+    pub pairs: Vec<AttemptComparison>,
+}
+
+/// Compares two attempts over the questions submitted in both.
+///
+/// Returns `None` if the attempts share no submitted questions.
+pub fn compare_attempts(
+    a: &Attempt,
+    b: &Attempt,
+    config: &CollusionConfig,
+) -> Option<AttemptComparison> {
+    let b_questions = submitted_questions(b);
+    let shared: Vec<(&AttemptQuestionSetQuestion, &AttemptQuestionSetQuestion)> =
+        submitted_questions(a)
+            .into_iter()
+            .filter_map(|qa| {
+                b_questions
+                    .iter()
+                    .find(|qb| qb.id == qa.id)
+                    .map(|qb| (qa, *qb))
+            })
+            .collect();
+
+    if shared.is_empty() {
+        return None;
+    }
+
+    let mut wrong_in_either = 0;
+    let mut identical_wrong_answers = 0;
+    let mut simultaneous = 0;
+    for (qa, qb) in shared.iter() {
+        let a_correct = compare_answers(&qa.answers, &qa.generated, &qa.selected);
+        let b_correct = compare_answers(&qb.answers, &qb.generated, &qb.selected);
+        if !a_correct || !b_correct {
+            wrong_in_either += 1;
+        }
+        if !a_correct && !b_correct && same_answers(&qa.selected, &qb.selected) {
+            identical_wrong_answers += 1;
+        }
+
+        if let (Some(ta), Some(tb)) = (qa.submission_time, qb.submission_time) {
+            let offset_in_ms = (ta.timestamp_millis() - tb.timestamp_millis()).abs();
+            if offset_in_ms as f64 / 1000.0 <= config.max_submission_offset_in_s {
+                simultaneous += 1;
+            }
+        }
+    }
+
+    let wrong_answer_similarity = if wrong_in_either == 0 {
+        0.0
+    } else {
+        identical_wrong_answers as f64 / wrong_in_either as f64
+    };
+    let timeline_similarity = simultaneous as f64 / shared.len() as f64;
+    let order_similarity = get_order_similarity(&shared);
+
+    let score = WRONG_ANSWER_WEIGHT * wrong_answer_similarity
+        + TIMELINE_WEIGHT * timeline_similarity
+        + ORDER_WEIGHT * order_similarity;
+
+    Some(AttemptComparison {
+        attempt_ids: [a.id, b.id],
+        user_ids: [a.user_id, b.user_id],
+        shared_questions: shared.len(),
+        identical_wrong_answers,
+        wrong_answer_similarity,
+        timeline_similarity,
+        order_similarity,
+        score,
+    })
+}
+
+impl AttemptComparison {
+    pub fn is_suspicious(&self, config: &CollusionConfig) -> bool {
+        self.shared_questions >= config.min_shared_questions
+            && self.identical_wrong_answers >= config.min_identical_wrong_answers
+            && self.score >= config.min_score
+    }
+}
+
+/// Compares all attempts of the same exam, and groups suspicious pairs into clusters.
+///
+/// Attempts by the same user, or started more than `max_start_time_difference_in_s` apart, are not compared.
+/// Clusters are ordered by exam id, then by their first attempt id.
+pub fn detect_collusion(attempts: &[Attempt], config: &CollusionConfig) -> Vec<CollusionCluster> {
+    let mut attempts_by_exam: BTreeMap<ObjectId, Vec<&Attempt>> = BTreeMap::new();
+    for attempt in attempts {
+        attempts_by_exam
+            .entry(attempt.exam_id)
+            .or_default()
+            .push(attempt);
+    }
+
+    let mut clusters = vec![];
+    for (exam_id, mut attempts) in attempts_by_exam {
+        attempts.sort_by_key(|a| a.id);
+
+        let mut pairs = vec![];
+        for (i, a) in attempts.iter().enumerate() {
+            for b in attempts.iter().skip(i + 1) {
+                if a.user_id == b.user_id {
+                    continue;
+                }
+                let start_time_difference_in_ms =
+                    (a.start_time.timestamp_millis() - b.start_time.timestamp_millis()).abs();
+                if start_time_difference_in_ms as f64 / 1000.0
+                    > config.max_start_time_difference_in_s
+                {
+                    continue;
+                }
+
+                if let Some(comparison) = compare_attempts(a, b, config)
+                    && comparison.is_suspicious(config)
+                {
+                    pairs.push(comparison);
+                }
+            }
+        }
+
+        clusters.extend(cluster_pairs(exam_id, pairs));
+    }
+
+    clusters
+}
+
+/// Groups pairs sharing an attempt into the same cluster
+fn cluster_pairs(exam_id: ObjectId, pairs: Vec<AttemptComparison>) -> Vec<CollusionCluster> {
+    let mut attempt_ids: Vec<ObjectId> = pairs.iter().flat_map(|p| p.attempt_ids).collect();
+    attempt_ids.sort();
+    attempt_ids.dedup();
+
+    // Union-find over indices into `attempt_ids`
+    let mut parents: Vec<usize> = (0..attempt_ids.len()).collect();
+    fn find(parents: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parents[root] != root {
+            root = parents[root];
+        }
+        parents[i] = root;
+        root
+    }
+
+    let index = |id: &ObjectId| attempt_ids.binary_search(id).expect("id is in attempt ids");
+    for pair in pairs.iter() {
+        let a = find(&mut parents, index(&pair.attempt_ids[0]));
+        let b = find(&mut parents, index(&pair.attempt_ids[1]));
+        if a != b {
+            parents[a.max(b)] = a.min(b);
+        }
+    }
+
+    let mut clusters: BTreeMap<usize, CollusionCluster> = BTreeMap::new();
+    for (i, id) in attempt_ids.iter().enumerate() {
+        let root = find(&mut parents, i);
+        clusters
+            .entry(root)
+            .or_insert_with(|| CollusionCluster {
+                exam_id,
+                attempt_ids: vec![],
+                pairs: vec![],
+            })
+            .attempt_ids
+            .push(*id);
+    }
+    for pair in pairs {
+        let root = find(&mut parents, index(&pair.attempt_ids[0]));
+        if let Some(cluster) = clusters.get_mut(&root) {
+            cluster.pairs.push(pair);
+        }
+    }
+
+    clusters.into_values().collect()
+}
+
+fn submitted_questions(attempt: &Attempt) -> Vec<&AttemptQuestionSetQuestion> {
+    attempt
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .filter(|q| q.submission_time.is_some())
+        .collect()
+}
+
+fn same_answers(a: &[ObjectId], b: &[ObjectId]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+/// Fraction of concordant pairs between the submission orders of both attempts
+fn get_order_similarity(
+    shared: &[(&AttemptQuestionSetQuestion, &AttemptQuestionSetQuestion)],
+) -> f64 {
+    let mut pairs = 0;
+    let mut concordant = 0;
+    for (i, (qa_i, qb_i)) in shared.iter().enumerate() {
+        for (qa_j, qb_j) in shared.iter().skip(i + 1) {
+            pairs += 1;
+            if (qa_i.submission_time < qa_j.submission_time)
+                == (qb_i.submission_time < qb_j.submission_time)
+            {
+                concordant += 1;
+            }
+        }
+    }
+
+    if pairs == 0 {
+        return 0.0;
+    }
+    concordant as f64 / pairs as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{DateTime, oid::ObjectId};
+    use prisma::{ExamEnvironmentAnswer, ExamEnvironmentConfig};
+
+    use crate::attempt::{Attempt, AttemptQuestionSet, AttemptQuestionSetQuestion};
+
+    use super::{CollusionConfig, compare_attempts, detect_collusion};
+
+    /// Question with a correct first answer and two incorrect answers
+    fn question(id: ObjectId, answers: &[ObjectId]) -> AttemptQuestionSetQuestion {
+        AttemptQuestionSetQuestion {
+            id,
+            text: String::new(),
+            tags: vec![],
+            deprecated: false,
+            audio: None,
+            answers: answers
+                .iter()
+                .enumerate()
+                .map(|(i, id)| ExamEnvironmentAnswer {
+                    id: *id,
+                    is_correct: i == 0,
+                    text: String::new(),
+                })
+                .collect(),
+            selected: vec![],
+            generated: answers.to_vec(),
+            submission_time: None,
+            dwell_time_in_s: 0.0,
+            visit_order: vec![],
+        }
+    }
+
+    /// Attempt answering each question with the answer at the given index, `offset_s` apart
+    fn attempt(
+        exam_id: ObjectId,
+        questions: &[AttemptQuestionSetQuestion],
+        selected: &[usize],
+        start_time: DateTime,
+        offset_s: i64,
+    ) -> Attempt {
+        let questions = questions
+            .iter()
+            .zip(selected)
+            .enumerate()
+            .map(|(i, (q, s))| AttemptQuestionSetQuestion {
+                selected: vec![q.answers[*s].id],
+                submission_time: Some(DateTime::from_millis(
+                    start_time.timestamp_millis() + (i as i64 + 1) * offset_s * 1000,
+                )),
+                ..q.clone()
+            })
+            .collect();
+
+        Attempt {
+            id: ObjectId::new(),
+            exam_id,
+            generated_exam_id: ObjectId::new(),
+            user_id: ObjectId::new(),
+            prerequisites: vec![],
+            deprecated: false,
+            question_sets: vec![AttemptQuestionSet {
+                id: ObjectId::new(),
+                _type: Default::default(),
+                context: None,
+                questions,
+            }],
+            config: ExamEnvironmentConfig::default(),
+            start_time,
+        }
+    }
+
+    fn questions(n: usize) -> Vec<AttemptQuestionSetQuestion> {
+        (0..n)
+            .map(|_| {
+                question(
+                    ObjectId::new(),
+                    &[ObjectId::new(), ObjectId::new(), ObjectId::new()],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compare_attempts_with_shared_wrong_answers() {
+        let exam_id = ObjectId::new();
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let questions = questions(6);
+        let config = CollusionConfig::default();
+
+        let a = attempt(exam_id, &questions, &[0, 1, 2, 0, 1, 0], start_time, 30);
+        let b = attempt(exam_id, &questions, &[0, 1, 2, 0, 2, 0], start_time, 31);
+
+        let comparison = compare_attempts(&a, &b, &config).unwrap();
+        assert_eq!(comparison.shared_questions, 6);
+        assert_eq!(comparison.identical_wrong_answers, 2);
+        assert!((comparison.wrong_answer_similarity - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(comparison.timeline_similarity, 1.0);
+        assert_eq!(comparison.order_similarity, 1.0);
+        assert!(comparison.is_suspicious(&config));
+
+        // Same answers submitted hours apart, without shared mistakes
+        let c = attempt(
+            exam_id,
+            &questions,
+            &[0, 0, 0, 0, 0, 0],
+            DateTime::from_millis(start_time.timestamp_millis() + 3 * 60 * 60 * 1000),
+            30,
+        );
+        let comparison = compare_attempts(&a, &c, &config).unwrap();
+        assert_eq!(comparison.identical_wrong_answers, 0);
+        assert_eq!(comparison.timeline_similarity, 0.0);
+        assert!(!comparison.is_suspicious(&config));
+    }
+
+    #[test]
+    fn collusion_clusters() {
+        let exam_id = ObjectId::new();
+        let other_exam_id = ObjectId::new();
+        let start_time = DateTime::from_millis(1_700_000_000_000);
+        let questions = questions(6);
+        let wrong = [1, 1, 2, 0, 0, 0];
+
+        let a = attempt(exam_id, &questions, &wrong, start_time, 30);
+        let b = attempt(exam_id, &questions, &wrong, start_time, 30);
+        let c = attempt(exam_id, &questions, &wrong, start_time, 32);
+        let honest = attempt(exam_id, &questions, &[0, 0, 0, 0, 0, 0], start_time, 60);
+        // Attempts of other exams are never compared
+        let other = attempt(other_exam_id, &questions, &wrong, start_time, 30);
+        // Retakes by the same user are not compared
+        let retake = Attempt {
+            id: ObjectId::new(),
+            ..a.clone()
+        };
+
+        let clusters = detect_collusion(
+            &[
+                a.clone(),
+                honest,
+                b.clone(),
+                other,
+                c.clone(),
+                retake.clone(),
+            ],
+            &CollusionConfig::default(),
+        );
+
+        assert_eq!(clusters.len(), 1);
+        let mut expected = vec![a.id, b.id, c.id, retake.id];
+        expected.sort();
+        assert_eq!(clusters[0].exam_id, exam_id);
+        assert_eq!(clusters[0].attempt_ids, expected);
+        // All pairs except the retake with the original attempt
+        assert_eq!(clusters[0].pairs.len(), 5);
+        assert!(
+            !clusters[0]
+                .pairs
+                .iter()
+                .any(|p| p.attempt_ids.contains(&a.id) && p.attempt_ids.contains(&retake.id))
+        );
+    }
+}
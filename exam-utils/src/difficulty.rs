@@ -0,0 +1,516 @@
+//! Difficulty-balanced generation
+//!
+//! The expected score of a generation is the mean estimated probability of answering each of its questions correctly.
+//! Generations outside the target band are repaired by swapping questions for unused questions of the same
+//! question set, covering the same `config.tags` groups, so every config constraint still holds.
+//!
+//! `generate_exam_with_difficulty`, and `try_generate` given difficulty options, balance and report the expected score.
+//! `generate_exam` and `generate_exam_with_seed` do neither; use `get_expected_score` on their generations.
+use std::collections::HashMap;
+
+use mongodb::bson::oid::ObjectId;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use crate::{
+    error::Error,
+    generation::validate_generation_against_exam,
+    misc::{
+        ExamInput, generate_exam_from_allocations, generate_exam_with_config_indices,
+        get_question_with_random_answers,
+    },
+    solver::QuestionSetAllocation,
+};
+
+/// Generations attempted, before giving up on the target band
+const DIFFICULTY_ATTEMPTS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyOptions {
+    /// Questions without an estimate are assumed to be of the mean estimated difficulty
+    pub difficulties: Vec<QuestionDifficulty>,
+    /// Lowest expected score, as a percentage
+    #[serde(rename = "minExpectedScore")]
+    pub min_expected_score: f64,
+    /// Highest expected score, as a percentage
+    #[serde(rename = "maxExpectedScore")]
+    pub max_expected_score: f64,
+    /// Seed to reproduce the generation. Random if not given.
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionDifficulty {
+    #[serde(rename = "questionId")]
+    pub question_id: ObjectId,
+    /// Estimated probability (0..=1) of answering the question correctly, such as an item analysis `difficulty`
+    pub difficulty: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DifficultyBalancedExam {
+    /// Seed the generation was generated from
+    pub seed: u64,
+    #[serde(rename = "generatedExam")]
+    pub generated_exam: prisma::ExamEnvironmentGeneratedExam,
+    /// Achieved expected score, as a percentage
+    #[serde(rename = "expectedScore")]
+    pub expected_score: f64,
+}
+
+/// Generates an exam with an expected score within `options.min_expected_score..=options.max_expected_score`,
+/// and validates it against the exam.
+///
+/// Errors if the exam cannot be generated, the options are invalid, or no generation within the band is found.
+pub fn generate_exam_with_difficulty(
+    exam: &ExamInput,
+    options: &DifficultyOptions,
+) -> Result<DifficultyBalancedExam, Error> {
+    let seed = options.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    generate_balanced_exam(exam, options, seed, &mut rng, None)
+}
+
+/// Generates an exam as `generate_exam_with_difficulty`, from `rng` seeded with `seed`.
+///
+/// The first generation is built from `allocations`, if given, instead of solving for them with `rng`.
+pub(crate) fn generate_balanced_exam(
+    exam: &ExamInput,
+    options: &DifficultyOptions,
+    seed: u64,
+    rng: &mut ChaCha8Rng,
+    mut allocations: Option<Vec<QuestionSetAllocation>>,
+) -> Result<DifficultyBalancedExam, Error> {
+    let difficulties = get_difficulties(exam, options)?;
+
+    let mut closest_expected_score = None;
+    for _ in 0..DIFFICULTY_ATTEMPTS {
+        let (mut generation, config_indices) = match allocations.take() {
+            Some(allocations) => generate_exam_from_allocations(exam, allocations, rng, None)?,
+            None => generate_exam_with_config_indices(exam, rng, None)?,
+        };
+        let expected_score = balance_generation(
+            exam,
+            &mut generation,
+            &config_indices,
+            &difficulties,
+            options,
+            rng,
+        )?;
+        trace!(expected_score, exam_id = %exam.id, "generation expected score");
+
+        if (options.min_expected_score..=options.max_expected_score).contains(&expected_score) {
+            validate_generation_against_exam(&exam.clone().into(), &generation)?;
+            return Ok(DifficultyBalancedExam {
+                seed,
+                generated_exam: generation,
+                expected_score,
+            });
+        }
+        let distance = get_distance(expected_score, options);
+        if closest_expected_score.is_none_or(|(d, _)| distance < d) {
+            closest_expected_score = Some((distance, expected_score));
+        }
+    }
+
+    Err(Error::Generation(format!(
+        "Unable to generate exam {} with an expected score between {}% and {}%. Closest expected score: {}%.",
+        exam.id,
+        options.min_expected_score,
+        options.max_expected_score,
+        closest_expected_score.map(|(_, s)| s).unwrap_or_default()
+    )))
+}
+
+/// Gets the expected score of a generation, as a percentage.
+///
+/// Questions without an estimate are assumed to be of the mean estimated difficulty.
+pub fn get_expected_score(
+    generation: &prisma::ExamEnvironmentGeneratedExam,
+    difficulties: &[QuestionDifficulty],
+) -> f64 {
+    let default_difficulty = if difficulties.is_empty() {
+        0.0
+    } else {
+        difficulties.iter().map(|d| d.difficulty).sum::<f64>() / difficulties.len() as f64
+    };
+    let difficulties: HashMap<ObjectId, f64> = difficulties
+        .iter()
+        .map(|d| (d.question_id, d.difficulty))
+        .collect();
+
+    let question_difficulties: Vec<f64> = generation
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .map(|q| {
+            difficulties
+                .get(&q.id)
+                .copied()
+                .unwrap_or(default_difficulty)
+        })
+        .collect();
+    if question_difficulties.is_empty() {
+        return 0.0;
+    }
+    question_difficulties.iter().sum::<f64>() / question_difficulties.len() as f64 * 100.0
+}
+
+/// Validates the options, and gets the estimated difficulty of every question in the exam
+fn get_difficulties(
+    exam: &ExamInput,
+    options: &DifficultyOptions,
+) -> Result<HashMap<ObjectId, f64>, Error> {
+    let band = options.min_expected_score..=options.max_expected_score;
+    if !(0.0..=100.0).contains(band.start())
+        || !(0.0..=100.0).contains(band.end())
+        || band.is_empty()
+    {
+        return Err(Error::Generation(format!(
+            "Invalid expected score band {}% to {}%. Must be within 0% to 100%.",
+            band.start(),
+            band.end()
+        )));
+    }
+    if options.difficulties.is_empty() {
+        return Err(Error::Generation(format!(
+            "No difficulty estimates given for exam {}.",
+            exam.id
+        )));
+    }
+    if let Some(d) = options
+        .difficulties
+        .iter()
+        .find(|d| !(0.0..=1.0).contains(&d.difficulty))
+    {
+        return Err(Error::Generation(format!(
+            "Invalid difficulty {} for question {}. Must be between 0 and 1.",
+            d.difficulty, d.question_id
+        )));
+    }
+
+    let default_difficulty = options
+        .difficulties
+        .iter()
+        .map(|d| d.difficulty)
+        .sum::<f64>()
+        / options.difficulties.len() as f64;
+    let estimates: HashMap<ObjectId, f64> = options
+        .difficulties
+        .iter()
+        .map(|d| (d.question_id, d.difficulty))
+        .collect();
+    Ok(exam
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .map(|q| {
+            (
+                q.id,
+                estimates.get(&q.id).copied().unwrap_or(default_difficulty),
+            )
+        })
+        .collect())
+}
+
+/// Percentage points the expected score is outside of the band
+fn get_distance(expected_score: f64, options: &DifficultyOptions) -> f64 {
+    (options.min_expected_score - expected_score)
+        .max(expected_score - options.max_expected_score)
+        .max(0.0)
+}
+
+/// Swaps questions for unused ones, one at a time, moving the expected score closest to the band,
+/// until it is within the band or no swap moves it closer. Returns the expected score.
+///
+/// `config_indices` is the index into `config.question_sets` each generated question set was allocated to.
+fn balance_generation(
+    exam: &ExamInput,
+    generation: &mut prisma::ExamEnvironmentGeneratedExam,
+    config_indices: &[usize],
+    difficulties: &HashMap<ObjectId, f64>,
+    options: &DifficultyOptions,
+    rng: &mut impl Rng,
+) -> Result<f64, Error> {
+    let number_of_questions = generation
+        .question_sets
+        .iter()
+        .map(|qs| qs.questions.len())
+        .sum::<usize>();
+    if number_of_questions == 0 {
+        return Ok(0.0);
+    }
+    let difficulty = |id: &ObjectId| difficulties.get(id).copied().unwrap_or_default();
+    let mut total: f64 = generation
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .map(|q| difficulty(&q.id))
+        .sum();
+    let expected_score = |total: f64| total / number_of_questions as f64 * 100.0;
+
+    let configs: Vec<&prisma::ExamEnvironmentQuestionSetConfig> = config_indices
+        .iter()
+        .map(|c| &exam.config.question_sets[*c])
+        .collect();
+
+    loop {
+        let distance = get_distance(expected_score(total), options);
+        if distance == 0.0 {
+            break;
+        }
+
+        // Generated question set index, generated question index, replacement question and resulting total
+        let mut best: Option<(
+            usize,
+            usize,
+            &prisma::ExamEnvironmentMultipleChoiceQuestion,
+            f64,
+        )> = None;
+        for (i, gqs) in generation.question_sets.iter().enumerate() {
+            let Some(question_set) = exam.question_sets.iter().find(|qs| qs.id == gqs.id) else {
+                continue;
+            };
+            let config = configs[i];
+            let unused = question_set.questions.iter().filter(|q| {
+                !q.deprecated
+                    && !gqs.questions.iter().any(|gq| gq.id == q.id)
+                    && has_enough_answers(q, config)
+            });
+            for candidate in unused {
+                let signature = get_tag_signature(candidate, &exam.config.tags);
+                for (j, gq) in gqs.questions.iter().enumerate() {
+                    let Some(question) = question_set.questions.iter().find(|q| q.id == gq.id)
+                    else {
+                        continue;
+                    };
+                    if get_tag_signature(question, &exam.config.tags) != signature {
+                        continue;
+                    }
+                    let swapped_total = total - difficulty(&gq.id) + difficulty(&candidate.id);
+                    let swapped_distance = get_distance(expected_score(swapped_total), options);
+                    let best_distance = best
+                        .map(|(_, _, _, t)| get_distance(expected_score(t), options))
+                        .unwrap_or(distance);
+                    if swapped_distance < best_distance {
+                        best = Some((i, j, candidate, swapped_total));
+                    }
+                }
+            }
+        }
+
+        let Some((i, j, candidate, swapped_total)) = best else {
+            break;
+        };
+        let question = get_question_with_random_answers(candidate, configs[i], rng, None)?;
+        generation.question_sets[i].questions[j] =
+            prisma::ExamEnvironmentGeneratedMultipleChoiceQuestion {
+                id: question.id,
+                answers: question.answers.into_iter().map(|a| a.id).collect(),
+            };
+        total = swapped_total;
+    }
+
+    Ok(expected_score(total))
+}
+
+fn has_enough_answers(
+    question: &prisma::ExamEnvironmentMultipleChoiceQuestion,
+    config: &prisma::ExamEnvironmentQuestionSetConfig,
+) -> bool {
+    let correct = question.answers.iter().filter(|a| a.is_correct).count();
+    let incorrect = question.answers.len() - correct;
+    correct as i64 >= config.number_of_correct_answers
+        && incorrect as i64 >= config.number_of_incorrect_answers
+}
+
+/// Whether the question covers each `config.tags` group
+fn get_tag_signature(
+    question: &prisma::ExamEnvironmentMultipleChoiceQuestion,
+    tags: &[prisma::ExamEnvironmentTagConfig],
+) -> Vec<bool> {
+    tags.iter()
+        .map(|t| t.group.iter().all(|tag| question.tags.contains(tag)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use prisma::{
+        ExamEnvironmentAnswer, ExamEnvironmentConfig, ExamEnvironmentMultipleChoiceQuestion,
+        ExamEnvironmentQuestionSet, ExamEnvironmentQuestionSetConfig, ExamEnvironmentQuestionType,
+    };
+
+    use crate::misc::ExamInput;
+
+    use super::{
+        DifficultyOptions, QuestionDifficulty, generate_exam_with_difficulty, get_expected_score,
+    };
+
+    /// Exam with one question set of 6 questions, generating 3 questions with 1 correct and 1 incorrect answer
+    fn exam() -> ExamInput {
+        let questions = (0..6)
+            .map(|_| ExamEnvironmentMultipleChoiceQuestion {
+                id: ObjectId::new(),
+                answers: [true, false]
+                    .iter()
+                    .map(|is_correct| ExamEnvironmentAnswer {
+                        id: ObjectId::new(),
+                        is_correct: *is_correct,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        ExamInput {
+            id: ObjectId::new(),
+            question_sets: vec![ExamEnvironmentQuestionSet {
+                id: ObjectId::new(),
+                questions,
+                ..Default::default()
+            }],
+            config: ExamEnvironmentConfig {
+                question_sets: vec![ExamEnvironmentQuestionSetConfig {
+                    number_of_set: 1,
+                    number_of_questions: 3,
+                    number_of_correct_answers: 1,
+                    number_of_incorrect_answers: 1,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Half the questions are easy (0.9), the other half hard (0.3)
+    fn options(
+        exam: &ExamInput,
+        min_expected_score: f64,
+        max_expected_score: f64,
+    ) -> DifficultyOptions {
+        let difficulties = exam.question_sets[0]
+            .questions
+            .iter()
+            .enumerate()
+            .map(|(i, q)| QuestionDifficulty {
+                question_id: q.id,
+                difficulty: if i < 3 { 0.9 } else { 0.3 },
+            })
+            .collect();
+        DifficultyOptions {
+            difficulties,
+            min_expected_score,
+            max_expected_score,
+            seed: Some(1),
+        }
+    }
+
+    #[test]
+    fn difficulty_band() {
+        let exam = exam();
+
+        for (min, max, expected) in [(85.0, 95.0, 90.0), (25.0, 35.0, 30.0), (45.0, 55.0, 50.0)] {
+            let options = options(&exam, min, max);
+            let generation = generate_exam_with_difficulty(&exam, &options).unwrap();
+            assert!((generation.expected_score - expected).abs() < 1e-9);
+            assert!(
+                (get_expected_score(&generation.generated_exam, &options.difficulties) - expected)
+                    .abs()
+                    < 1e-9
+            );
+            assert_eq!(
+                generation.generated_exam.question_sets[0].questions.len(),
+                3
+            );
+        }
+
+        // No 3 questions are easier than the 3 easy questions
+        assert!(generate_exam_with_difficulty(&exam, &options(&exam, 95.0, 100.0)).is_err());
+        assert!(generate_exam_with_difficulty(&exam, &options(&exam, 60.0, 50.0)).is_err());
+    }
+
+    #[test]
+    fn expected_score_without_estimates() {
+        let exam = exam();
+        let mut options = options(&exam, 0.0, 100.0);
+        // Questions without an estimate are of the mean estimated difficulty: (0.9 + 0.3) / 2
+        options.difficulties = vec![
+            options.difficulties.remove(0),
+            options.difficulties.remove(4),
+        ];
+        let generation = generate_exam_with_difficulty(&exam, &options).unwrap();
+
+        let mut expected_score = 0.0;
+        for question in generation.generated_exam.question_sets[0].questions.iter() {
+            expected_score += options
+                .difficulties
+                .iter()
+                .find(|d| d.question_id == question.id)
+                .map(|d| d.difficulty)
+                .unwrap_or(0.6);
+        }
+        expected_score = expected_score / 3.0 * 100.0;
+        assert!((generation.expected_score - expected_score).abs() < 1e-9);
+    }
+
+    #[test]
+    fn balance_keeps_config_slots() {
+        // A dialogue set generating 2 incorrect answers, alongside the multiple choice set generating 1
+        let mut exam = exam();
+        let mut dialogue = exam.question_sets[0].clone();
+        dialogue.id = ObjectId::new();
+        dialogue._type = ExamEnvironmentQuestionType::Dialogue;
+        for question in dialogue.questions.iter_mut() {
+            question.id = ObjectId::new();
+            for answer in question.answers.iter_mut() {
+                answer.id = ObjectId::new();
+            }
+            question.answers.push(ExamEnvironmentAnswer {
+                id: ObjectId::new(),
+                is_correct: false,
+                ..Default::default()
+            });
+        }
+        exam.question_sets.insert(0, dialogue);
+        exam.config.question_sets.insert(
+            0,
+            ExamEnvironmentQuestionSetConfig {
+                _type: ExamEnvironmentQuestionType::Dialogue,
+                number_of_incorrect_answers: 2,
+                ..exam.config.question_sets[0].clone()
+            },
+        );
+
+        let mut options = options(&exam, 85.0, 95.0);
+        options.difficulties = exam
+            .question_sets
+            .iter()
+            .flat_map(|qs| qs.questions.iter().enumerate())
+            .map(|(i, q)| QuestionDifficulty {
+                question_id: q.id,
+                difficulty: if i < 3 { 0.9 } else { 0.3 },
+            })
+            .collect();
+        let generation = generate_exam_with_difficulty(&exam, &options).unwrap();
+        assert!((generation.expected_score - 90.0).abs() < 1e-9);
+
+        for generated_set in generation.generated_exam.question_sets.iter() {
+            let question_set = exam
+                .question_sets
+                .iter()
+                .find(|qs| qs.id == generated_set.id)
+                .unwrap();
+            let number_of_answers = match question_set._type {
+                ExamEnvironmentQuestionType::Dialogue => 3,
+                ExamEnvironmentQuestionType::MultipleChoice => 2,
+            };
+            for question in generated_set.questions.iter() {
+                assert_eq!(question.answers.len(), number_of_answers);
+            }
+        }
+    }
+}
@@ -6,6 +6,10 @@ pub enum Error {
     InvalidConfig(String),
     #[error("{0}")]
     ModerationScore(String),
+    #[error("{0}")]
+    Score(String),
+    #[error("{0}")]
+    EventMeta(String),
     // Froms
     #[error("{0}")]
     MongoDB(#[from] mongodb::error::Error),
@@ -0,0 +1,597 @@
+//! Exposure-controlled batch generation
+//!
+//! Generations in a batch are created one after another, each preferring the question sets, questions and answers
+//! exposed the least in the generations before it.
+//!
+//! `estimate_generation` instead generates independently many times, estimating how often each question is exposed,
+//! and how often generation fails once random questions are removed.
+use std::collections::{HashMap, HashSet};
+
+use mongodb::bson::oid::ObjectId;
+use rand::{Rng, SeedableRng, seq::IndexedRandom};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    misc::{ExamInput, generate_exam_with_exposure, get_infeasible_error},
+    solver::{Feasibility, get_feasibility, is_feasible},
+};
+
+/// Number of generations each question set, question and answer id appears in
+pub(crate) type ExposureCounts = HashMap<ObjectId, usize>;
+
+/// Attempts at each generation, before giving up on the max pairwise overlap
+const OVERLAP_ATTEMPTS: usize = 20;
+/// Largest random estimate seed, so the seed is exactly representable as a JavaScript number
+const MAX_SAFE_SEED: u64 = (1 << 53) - 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchOptions {
+    #[serde(rename = "numberOfGenerations")]
+    pub number_of_generations: usize,
+    /// Most questions two generations may share, as a fraction (0..=1) of the questions in a generation
+    #[serde(rename = "maxPairwiseOverlap")]
+    pub max_pairwise_overlap: Option<f64>,
+    /// Seed to reproduce the batch. Random if not given.
+    pub seed: Option<u64>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            number_of_generations: 10,
+            max_pairwise_overlap: None,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationBatch {
+    /// Seed the batch was generated from
+    pub seed: u64,
+    pub generations: Vec<prisma::ExamEnvironmentGeneratedExam>,
+    pub exposure: ExposureStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExposureStats {
+    #[serde(rename = "numberOfGenerations")]
+    pub number_of_generations: usize,
+    /// Every non-deprecated question, including those never exposed
+    pub questions: Vec<ItemExposure>,
+    /// Every answer of a non-deprecated question, including those never exposed
+    pub answers: Vec<ItemExposure>,
+    #[serde(rename = "maxPairwiseOverlap")]
+    pub max_pairwise_overlap: f64,
+    #[serde(rename = "meanPairwiseOverlap")]
+    pub mean_pairwise_overlap: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemExposure {
+    pub id: ObjectId,
+    /// Number of generations the item appears in
+    pub count: usize,
+    /// `count` as a fraction of all generations
+    pub rate: f64,
+}
+
+/// Generates `options.number_of_generations` generations, balancing how often each question and answer is exposed.
+///
+/// Errors if the exam cannot be generated, or a generation within `options.max_pairwise_overlap` is not found.
+pub fn generate_exam_batch(
+    exam: &ExamInput,
+    options: &BatchOptions,
+) -> Result<GenerationBatch, Error> {
+    if let Some(max_pairwise_overlap) = options.max_pairwise_overlap
+        && !(0.0..=1.0).contains(&max_pairwise_overlap)
+    {
+        return Err(Error::Generation(format!(
+            "Invalid max pairwise overlap {max_pairwise_overlap}. Must be between 0 and 1."
+        )));
+    }
+
+    let seed = options.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut exposure = ExposureCounts::new();
+    let mut generations: Vec<prisma::ExamEnvironmentGeneratedExam> = vec![];
+    let mut generation_questions: Vec<HashSet<ObjectId>> = vec![];
+
+    for i in 0..options.number_of_generations {
+        let mut accepted = None;
+        for _ in 0..OVERLAP_ATTEMPTS {
+            let generation = generate_exam_with_exposure(exam, &mut rng, Some(&exposure))?;
+            let questions = get_question_ids(&generation);
+            let within_overlap = options.max_pairwise_overlap.is_none_or(|max| {
+                generation_questions
+                    .iter()
+                    .all(|other| get_overlap(&questions, other) <= max)
+            });
+            if within_overlap {
+                accepted = Some((generation, questions));
+                break;
+            }
+        }
+
+        let Some((generation, questions)) = accepted else {
+            return Err(Error::Generation(format!(
+                "Unable to generate generation {} of {} for exam {} within a max pairwise overlap of {}.",
+                i + 1,
+                options.number_of_generations,
+                exam.id,
+                options.max_pairwise_overlap.unwrap_or(1.0)
+            )));
+        };
+
+        for question_set in generation.question_sets.iter() {
+            *exposure.entry(question_set.id).or_default() += 1;
+            for question in question_set.questions.iter() {
+                *exposure.entry(question.id).or_default() += 1;
+                for answer in question.answers.iter() {
+                    *exposure.entry(*answer).or_default() += 1;
+                }
+            }
+        }
+        generations.push(generation);
+        generation_questions.push(questions);
+    }
+
+    let exposure = get_exposure_stats(exam, &generations);
+    Ok(GenerationBatch {
+        seed,
+        generations,
+        exposure,
+    })
+}
+
+/// Gets how often each question and answer of the exam is exposed in the generations,
+/// and how many questions pairs of generations share.
+pub fn get_exposure_stats(
+    exam: &ExamInput,
+    generations: &[prisma::ExamEnvironmentGeneratedExam],
+) -> ExposureStats {
+    let number_of_generations = generations.len();
+
+    let mut counts = ExposureCounts::new();
+    for generation in generations {
+        for question_set in generation.question_sets.iter() {
+            for question in question_set.questions.iter() {
+                *counts.entry(question.id).or_default() += 1;
+                for answer in question.answers.iter() {
+                    *counts.entry(*answer).or_default() += 1;
+                }
+            }
+        }
+    }
+    let item_exposure = |id: ObjectId| {
+        let count = counts.get(&id).copied().unwrap_or(0);
+        let rate = if number_of_generations == 0 {
+            0.0
+        } else {
+            count as f64 / number_of_generations as f64
+        };
+        ItemExposure { id, count, rate }
+    };
+
+    let exam_questions = exam
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .filter(|q| !q.deprecated);
+    let questions = exam_questions
+        .clone()
+        .map(|q| item_exposure(q.id))
+        .collect();
+    let answers = exam_questions
+        .flat_map(|q| q.answers.iter())
+        .map(|a| item_exposure(a.id))
+        .collect();
+
+    let generation_questions: Vec<HashSet<ObjectId>> =
+        generations.iter().map(get_question_ids).collect();
+    let mut overlaps = vec![];
+    for (i, questions) in generation_questions.iter().enumerate() {
+        for other in generation_questions.iter().skip(i + 1) {
+            overlaps.push(get_overlap(questions, other));
+        }
+    }
+    let max_pairwise_overlap = overlaps.iter().copied().fold(0.0, f64::max);
+    let mean_pairwise_overlap = if overlaps.is_empty() {
+        0.0
+    } else {
+        overlaps.iter().sum::<f64>() / overlaps.len() as f64
+    };
+
+    ExposureStats {
+        number_of_generations,
+        questions,
+        answers,
+        max_pairwise_overlap,
+        mean_pairwise_overlap,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EstimateOptions {
+    #[serde(rename = "numberOfRuns")]
+    pub number_of_runs: usize,
+    /// Most questions removed when estimating fragility
+    #[serde(rename = "maxRemovedQuestions")]
+    pub max_removed_questions: usize,
+    /// Runs for each number of removed questions
+    #[serde(rename = "fragilityRuns")]
+    pub fragility_runs: usize,
+    /// Seed to reproduce the estimate. Random if not given.
+    pub seed: Option<u64>,
+}
+
+impl Default for EstimateOptions {
+    fn default() -> Self {
+        Self {
+            number_of_runs: 1000,
+            max_removed_questions: 5,
+            fragility_runs: 100,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationEstimate {
+    /// Seed the runs were generated from
+    pub seed: u64,
+    #[serde(rename = "numberOfRuns")]
+    pub number_of_runs: usize,
+    #[serde(rename = "numberOfFailures")]
+    pub number_of_failures: usize,
+    /// `number_of_failures` as a fraction of all runs
+    #[serde(rename = "failureRate")]
+    pub failure_rate: f64,
+    /// Distinct generation errors, most frequent first
+    #[serde(rename = "failureReasons")]
+    pub failure_reasons: Vec<FailureReason>,
+    /// Every non-deprecated question. `rate` is the probability of the question being selected in a successful run.
+    pub questions: Vec<ItemExposure>,
+    /// Non-deprecated questions not selected in any run
+    #[serde(rename = "neverSelected")]
+    pub never_selected: Vec<ObjectId>,
+    /// Failure rate for each number of removed questions, from 1 to `max_removed_questions`
+    pub fragility: Vec<Fragility>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fragility {
+    /// Number of random non-deprecated questions deprecated in each run
+    #[serde(rename = "removedQuestions")]
+    pub removed_questions: usize,
+    #[serde(rename = "numberOfRuns")]
+    pub number_of_runs: usize,
+    #[serde(rename = "numberOfFailures")]
+    pub number_of_failures: usize,
+    /// `number_of_failures` as a fraction of all runs
+    #[serde(rename = "failureRate")]
+    pub failure_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureReason {
+    pub reason: String,
+    /// Number of runs failing for the reason
+    pub count: usize,
+}
+
+/// Generates the exam `options.number_of_runs` times, independently of each other,
+/// and reports how often generation fails and how often each question is selected.
+///
+/// The solver finds a generation whenever one exists, so generation only fails if the config is infeasible,
+/// whatever the seed. Feasibility is checked once, and an infeasible config fails every run without generating.
+///
+/// How fragile the config is, is estimated by deprecating random questions, and checking feasibility again:
+/// for each number of removed questions, `options.fragility_runs` times.
+pub fn estimate_generation(exam: &ExamInput, options: &EstimateOptions) -> GenerationEstimate {
+    let seed = options
+        .seed
+        .unwrap_or_else(|| rand::rng().random_range(0..=MAX_SAFE_SEED));
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut counts = ExposureCounts::new();
+    let mut failure_reasons: Vec<FailureReason> = vec![];
+    let feasible = match get_feasibility(&exam.question_sets, &exam.config) {
+        Feasibility::Feasible(_) => true,
+        Feasibility::Infeasible(constraints) => {
+            if options.number_of_runs > 0 {
+                failure_reasons.push(FailureReason {
+                    reason: get_infeasible_error(exam, &constraints).to_string(),
+                    count: options.number_of_runs,
+                });
+            }
+            false
+        }
+    };
+    for _ in 0..options.number_of_runs {
+        if !feasible {
+            break;
+        }
+        match generate_exam_with_exposure(exam, &mut rng, None) {
+            Ok(generation) => {
+                for question_id in get_question_ids(&generation) {
+                    *counts.entry(question_id).or_default() += 1;
+                }
+            }
+            Err(e) => {
+                let reason = e.to_string();
+                match failure_reasons.iter_mut().find(|f| f.reason == reason) {
+                    Some(failure_reason) => failure_reason.count += 1,
+                    None => failure_reasons.push(FailureReason { reason, count: 1 }),
+                }
+            }
+        }
+    }
+    failure_reasons.sort_by_key(|f| std::cmp::Reverse(f.count));
+
+    let number_of_failures: usize = failure_reasons.iter().map(|f| f.count).sum();
+    let number_of_successes = options.number_of_runs - number_of_failures;
+    let failure_rate = if options.number_of_runs == 0 {
+        0.0
+    } else {
+        number_of_failures as f64 / options.number_of_runs as f64
+    };
+
+    let questions: Vec<ItemExposure> = exam
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter())
+        .filter(|q| !q.deprecated)
+        .map(|q| {
+            let count = counts.get(&q.id).copied().unwrap_or(0);
+            let rate = if number_of_successes == 0 {
+                0.0
+            } else {
+                count as f64 / number_of_successes as f64
+            };
+            ItemExposure {
+                id: q.id,
+                count,
+                rate,
+            }
+        })
+        .collect();
+    let never_selected = questions
+        .iter()
+        .filter(|q| q.count == 0)
+        .map(|q| q.id)
+        .collect();
+
+    let fragility = get_fragility(exam, options, feasible, &mut rng);
+
+    GenerationEstimate {
+        seed,
+        number_of_runs: options.number_of_runs,
+        number_of_failures,
+        failure_rate,
+        failure_reasons,
+        questions,
+        never_selected,
+        fragility,
+    }
+}
+
+/// Failure rate of the config after deprecating random questions, for each number of removed questions.
+///
+/// Removing questions never makes an infeasible config feasible, so every run of an infeasible config fails.
+fn get_fragility(
+    exam: &ExamInput,
+    options: &EstimateOptions,
+    feasible: bool,
+    rng: &mut impl Rng,
+) -> Vec<Fragility> {
+    // Non-deprecated questions, as (question set index, question index)
+    let usable: Vec<(usize, usize)> = exam
+        .question_sets
+        .iter()
+        .enumerate()
+        .flat_map(|(s, qs)| {
+            qs.questions
+                .iter()
+                .enumerate()
+                .filter(|(_, q)| !q.deprecated)
+                .map(move |(q, _)| (s, q))
+        })
+        .collect();
+
+    (1..=options.max_removed_questions.min(usable.len()))
+        .map(|removed_questions| {
+            let number_of_failures = if feasible {
+                (0..options.fragility_runs)
+                    .filter(|_| {
+                        let mut question_sets = exam.question_sets.clone();
+                        for (s, q) in usable.choose_multiple(rng, removed_questions) {
+                            question_sets[*s].questions[*q].deprecated = true;
+                        }
+                        !is_feasible(&question_sets, &exam.config)
+                    })
+                    .count()
+            } else {
+                options.fragility_runs
+            };
+            let failure_rate = if options.fragility_runs == 0 {
+                0.0
+            } else {
+                number_of_failures as f64 / options.fragility_runs as f64
+            };
+            Fragility {
+                removed_questions,
+                number_of_runs: options.fragility_runs,
+                number_of_failures,
+                failure_rate,
+            }
+        })
+        .collect()
+}
+
+fn get_question_ids(generation: &prisma::ExamEnvironmentGeneratedExam) -> HashSet<ObjectId> {
+    generation
+        .question_sets
+        .iter()
+        .flat_map(|qs| qs.questions.iter().map(|q| q.id))
+        .collect()
+}
+
+/// Shared questions, as a fraction of the questions in the larger generation
+fn get_overlap(a: &HashSet<ObjectId>, b: &HashSet<ObjectId>) -> f64 {
+    let size = a.len().max(b.len());
+    if size == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / size as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use prisma::{
+        ExamEnvironmentAnswer, ExamEnvironmentConfig, ExamEnvironmentMultipleChoiceQuestion,
+        ExamEnvironmentQuestionSet, ExamEnvironmentQuestionSetConfig,
+    };
+
+    use crate::misc::ExamInput;
+
+    use super::{BatchOptions, EstimateOptions, estimate_generation, generate_exam_batch};
+
+    /// Exam with one question set of 6 questions, generating 2 questions with 1 correct and 1 incorrect answer
+    fn exam() -> ExamInput {
+        let questions = (0..6)
+            .map(|_| ExamEnvironmentMultipleChoiceQuestion {
+                id: ObjectId::new(),
+                answers: [true, false, false]
+                    .iter()
+                    .map(|is_correct| ExamEnvironmentAnswer {
+                        id: ObjectId::new(),
+                        is_correct: *is_correct,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        ExamInput {
+            id: ObjectId::new(),
+            question_sets: vec![ExamEnvironmentQuestionSet {
+                id: ObjectId::new(),
+                questions,
+                ..Default::default()
+            }],
+            config: ExamEnvironmentConfig {
+                question_sets: vec![ExamEnvironmentQuestionSetConfig {
+                    number_of_set: 1,
+                    number_of_questions: 2,
+                    number_of_correct_answers: 1,
+                    number_of_incorrect_answers: 1,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn balanced_batch() {
+        let exam = exam();
+        let options = BatchOptions {
+            number_of_generations: 6,
+            max_pairwise_overlap: Some(0.5),
+            seed: Some(1),
+        };
+
+        let batch = generate_exam_batch(&exam, &options).unwrap();
+        assert_eq!(batch.seed, 1);
+        assert_eq!(batch.generations.len(), 6);
+        // 12 questions exposed over 6 questions
+        assert!(batch.exposure.questions.iter().all(|q| q.count == 2));
+        assert!((batch.exposure.questions[0].rate - 1.0 / 3.0).abs() < 1e-9);
+        // Each question exposes its correct answer, and alternates between its two incorrect answers
+        let answer_counts: Vec<usize> = batch.exposure.answers.iter().map(|a| a.count).collect();
+        assert_eq!(answer_counts, [2, 1, 1].repeat(6));
+        assert!(batch.exposure.max_pairwise_overlap <= 0.5);
+
+        let again = generate_exam_batch(&exam, &options).unwrap();
+        let question_sets = |batch: &super::GenerationBatch| {
+            batch
+                .generations
+                .iter()
+                .map(|g| g.question_sets.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(question_sets(&batch), question_sets(&again));
+    }
+
+    #[test]
+    fn batch_overlap_too_low() {
+        // Only 3 generations of 2 questions can be disjoint
+        let options = BatchOptions {
+            number_of_generations: 4,
+            max_pairwise_overlap: Some(0.0),
+            seed: Some(1),
+        };
+        assert!(generate_exam_batch(&exam(), &options).is_err());
+    }
+
+    #[test]
+    fn generation_estimate() {
+        let mut exam = exam();
+        exam.question_sets[0].questions[5].deprecated = true;
+        // Never enough incorrect answers
+        exam.question_sets[0].questions[4]
+            .answers
+            .retain(|a| a.is_correct);
+        let options = EstimateOptions {
+            number_of_runs: 200,
+            max_removed_questions: 4,
+            fragility_runs: 200,
+            seed: Some(1),
+        };
+
+        let estimate = estimate_generation(&exam, &options);
+        assert_eq!(estimate.number_of_runs, 200);
+        assert_eq!(estimate.number_of_failures, 0);
+        assert_eq!(estimate.questions.len(), 5);
+        // 2 of the 4 usable questions are selected in each run
+        for question in estimate.questions.iter().take(4) {
+            assert!((question.rate - 0.5).abs() < 0.15);
+        }
+        assert_eq!(
+            estimate.never_selected,
+            vec![exam.question_sets[0].questions[4].id]
+        );
+        // Removing 3 of the 5 non-deprecated questions fails if all 3 are usable: 4 of 10 ways
+        let failure_rates: Vec<f64> = estimate.fragility.iter().map(|f| f.failure_rate).collect();
+        assert_eq!(failure_rates.len(), 4);
+        assert_eq!(failure_rates[..2], [0.0, 0.0]);
+        assert!((failure_rates[2] - 0.4).abs() < 0.1);
+        assert_eq!(failure_rates[3], 1.0);
+
+        // Not enough usable questions
+        exam.config.question_sets[0].number_of_questions = 5;
+        let estimate = estimate_generation(&exam, &options);
+        assert_eq!(estimate.failure_rate, 1.0);
+        assert_eq!(estimate.failure_reasons.len(), 1);
+        assert_eq!(estimate.failure_reasons[0].count, 200);
+        assert!(
+            estimate.failure_reasons[0]
+                .reason
+                .contains("Conflicting constraints")
+        );
+        assert_eq!(estimate.never_selected.len(), 5);
+        assert!(estimate.fragility.iter().all(|f| f.failure_rate == 1.0));
+
+        // Missing options are defaulted, and random seeds are exactly representable in JavaScript
+        let options: EstimateOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options.number_of_runs, 1000);
+        let estimate = estimate_generation(&exam, &options);
+        assert!(estimate.seed < 1 << 53);
+    }
+}
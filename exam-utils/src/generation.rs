@@ -1,13 +1,83 @@
-use crate::error::Error;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
 
-/// Given an exam, use config to create generations.
+use crate::{
+    difficulty::{DifficultyOptions, generate_balanced_exam},
+    error::Error,
+    misc::{ExamInput, generate_exam_from_allocations, validate_config_with},
+    solver::solve_with_rng,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatedGeneratedExam {
+    #[serde(rename = "generatedExam")]
+    pub generated_exam: prisma::ExamEnvironmentGeneratedExam,
+    /// Achieved expected score, as a percentage. `None` if no difficulty options were given.
+    #[serde(rename = "expectedScore")]
+    pub expected_score: Option<f64>,
+}
+
+/// Given an exam, use config to create a generation.
 ///
-/// Can fail if config is invalid, or if algorithm gets stuck.
-pub fn try_generate<E>(_exam: E) -> Result<prisma::ExamEnvironmentGeneratedExam, Error>
+/// Validates the config, generates, and validates the generation against the exam.
+/// The generation is built from the allocation found whilst validating the config, so the solver runs once.
+/// If `difficulty` is given, the expected score is kept within its band, as `generate_exam_with_difficulty` does,
+/// and reported.
+/// Fails with `Error::InvalidConfig` if the config has errors, or `Error::Generation` if generation fails.
+pub fn try_generate<E>(
+    exam: E,
+    difficulty: Option<&DifficultyOptions>,
+) -> Result<ValidatedGeneratedExam, Error>
 where
     E: Into<prisma::ExamEnvironmentExam>,
 {
-    unimplemented!()
+    let exam: prisma::ExamEnvironmentExam = exam.into();
+    let input = ExamInput::from(exam.clone());
+
+    let seed = difficulty
+        .and_then(|d| d.seed)
+        .unwrap_or_else(|| rand::rng().random());
+    trace!(seed, exam_id = %exam.id, "generating exam");
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let (issues, allocations) = validate_config_with(&exam, || {
+        solve_with_rng(&input.question_sets, &input.config, Some(&mut rng), None)
+    });
+    let errors: Vec<String> = issues
+        .into_iter()
+        .filter(|issue| issue.is_error())
+        .map(|issue| issue.message)
+        .collect();
+    if !errors.is_empty() {
+        return Err(Error::InvalidConfig(format!(
+            "Invalid exam config for {}: {}",
+            exam.id,
+            errors.join("; ")
+        )));
+    }
+    let allocations = allocations.ok_or_else(|| {
+        Error::Generation(format!("No question sets allocated for exam {}.", exam.id))
+    })?;
+
+    if let Some(difficulty) = difficulty {
+        // The balanced generation is validated against the exam
+        let balanced =
+            generate_balanced_exam(&input, difficulty, seed, &mut rng, Some(allocations))?;
+        return Ok(ValidatedGeneratedExam {
+            generated_exam: balanced.generated_exam,
+            expected_score: Some(balanced.expected_score),
+        });
+    }
+
+    let (generation, _) = generate_exam_from_allocations(&input, allocations, &mut rng, None)?;
+    validate_generation_against_exam(&exam, &generation)?;
+
+    Ok(ValidatedGeneratedExam {
+        generated_exam: generation,
+        expected_score: None,
+    })
 }
 
 /// Given a generation, validate it for basic properties:
@@ -87,3 +157,327 @@ pub fn validate_generation(generation: &prisma::ExamEnvironmentGeneratedExam) ->
 
     Ok(())
 }
+
+/// Given an exam and one of its generations, validate the generation matches the exam config:
+/// 1) No duplicates
+/// 2) Every question set, question and answer exists in the exam, and no question is deprecated
+/// 3) Question sets match the question set configs in type, number of sets, questions, and correct/incorrect answers
+/// 4) Every tag config is met
+pub fn validate_generation_against_exam(
+    exam: &prisma::ExamEnvironmentExam,
+    generation: &prisma::ExamEnvironmentGeneratedExam,
+) -> Result<(), Error> {
+    validate_generation(generation)?;
+
+    if generation.exam_id != exam.id {
+        return Err(Error::Generation(format!(
+            "generation {} is for exam {}, not exam {}",
+            generation.id, generation.exam_id, exam.id
+        )));
+    }
+
+    // Generated question sets of each shape: (type, number of questions, correct answers, incorrect answers)
+    let mut shapes: Vec<(QuestionSetShape, i64)> = vec![];
+    let mut questions: Vec<&prisma::ExamEnvironmentMultipleChoiceQuestion> = vec![];
+    for generated_set in generation.question_sets.iter() {
+        let question_set = exam
+            .question_sets
+            .iter()
+            .find(|qs| qs.id == generated_set.id)
+            .ok_or_else(|| {
+                Error::Generation(format!(
+                    "question set {} not found in exam {}",
+                    generated_set.id, exam.id
+                ))
+            })?;
+
+        let mut answer_counts = vec![];
+        for generated_question in generated_set.questions.iter() {
+            let question = question_set
+                .questions
+                .iter()
+                .find(|q| q.id == generated_question.id)
+                .ok_or_else(|| {
+                    Error::Generation(format!(
+                        "question {} not found in question set {}",
+                        generated_question.id, question_set.id
+                    ))
+                })?;
+            if question.deprecated {
+                return Err(Error::Generation(format!(
+                    "question {} is deprecated",
+                    question.id
+                )));
+            }
+
+            let mut correct = 0;
+            for answer_id in generated_question.answers.iter() {
+                let answer = question
+                    .answers
+                    .iter()
+                    .find(|a| a.id == *answer_id)
+                    .ok_or_else(|| {
+                        Error::Generation(format!(
+                            "answer {} not found in question {}",
+                            answer_id, question.id
+                        ))
+                    })?;
+                if answer.is_correct {
+                    correct += 1;
+                }
+            }
+            answer_counts.push((correct, generated_question.answers.len() as i64 - correct));
+            questions.push(question);
+        }
+
+        // Every question in a set is generated from the same config
+        let (correct, incorrect) = answer_counts.first().copied().unwrap_or((0, 0));
+        if answer_counts.iter().any(|c| *c != (correct, incorrect)) {
+            return Err(Error::Generation(format!(
+                "questions in question set {} have different numbers of correct/incorrect answers",
+                generated_set.id
+            )));
+        }
+        let shape = QuestionSetShape {
+            _type: question_set._type.clone(),
+            number_of_questions: generated_set.questions.len() as i64,
+            number_of_correct_answers: correct,
+            number_of_incorrect_answers: incorrect,
+        };
+        match shapes.iter_mut().find(|(s, _)| *s == shape) {
+            Some((_, count)) => *count += 1,
+            None => shapes.push((shape, 1)),
+        }
+    }
+
+    // Configs of the same shape are interchangeable, so only the number of sets of each shape matters
+    let mut expected: Vec<(QuestionSetShape, i64)> = vec![];
+    for config in exam.config.question_sets.iter() {
+        let shape = QuestionSetShape {
+            _type: config._type.clone(),
+            number_of_questions: config.number_of_questions,
+            number_of_correct_answers: config.number_of_correct_answers,
+            number_of_incorrect_answers: config.number_of_incorrect_answers,
+        };
+        match expected.iter_mut().find(|(s, _)| *s == shape) {
+            Some((_, count)) => *count += config.number_of_set,
+            None => expected.push((shape, config.number_of_set)),
+        }
+    }
+    for (shape, count) in shapes.iter() {
+        let expected_count = expected
+            .iter()
+            .find(|(s, _)| s == shape)
+            .map(|(_, c)| *c)
+            .unwrap_or(0);
+        if *count != expected_count {
+            return Err(Error::Generation(format!(
+                "generation has {count} {:?} question set(s) of {} question(s) with {} correct and {} incorrect answer(s), config expects {expected_count}",
+                shape._type,
+                shape.number_of_questions,
+                shape.number_of_correct_answers,
+                shape.number_of_incorrect_answers
+            )));
+        }
+    }
+    if let Some((shape, count)) = expected
+        .iter()
+        .find(|(s, c)| *c > 0 && !shapes.iter().any(|(shape, _)| shape == s))
+    {
+        return Err(Error::Generation(format!(
+            "generation has no {:?} question sets of {} question(s) with {} correct and {} incorrect answer(s), config expects {count}",
+            shape._type,
+            shape.number_of_questions,
+            shape.number_of_correct_answers,
+            shape.number_of_incorrect_answers
+        )));
+    }
+
+    for tag_config in exam.config.tags.iter() {
+        let count = questions
+            .iter()
+            .filter(|q| tag_config.group.iter().all(|t| q.tags.contains(t)))
+            .count() as i64;
+        if count < tag_config.number_of_questions {
+            return Err(Error::Generation(format!(
+                "generation has {count} question(s) tagged \"{}\", config expects {}",
+                tag_config.group.join(","),
+                tag_config.number_of_questions
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(PartialEq)]
+struct QuestionSetShape {
+    _type: prisma::ExamEnvironmentQuestionType,
+    number_of_questions: i64,
+    number_of_correct_answers: i64,
+    number_of_incorrect_answers: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use prisma::{
+        ExamEnvironmentAnswer, ExamEnvironmentConfig, ExamEnvironmentExam,
+        ExamEnvironmentGeneratedMultipleChoiceQuestion, ExamEnvironmentGeneratedQuestionSet,
+        ExamEnvironmentMultipleChoiceQuestion, ExamEnvironmentQuestionSet,
+        ExamEnvironmentQuestionSetConfig, ExamEnvironmentTagConfig,
+    };
+
+    use crate::{
+        difficulty::{DifficultyOptions, QuestionDifficulty, get_expected_score},
+        error::Error,
+        misc::{ExamInput, generate_exam},
+    };
+
+    use super::{try_generate, validate_generation_against_exam};
+
+    /// Exam with two question sets of 3 questions tagged "a", generating one set of 2 questions
+    fn exam() -> ExamEnvironmentExam {
+        let question_sets = (0..2)
+            .map(|_| ExamEnvironmentQuestionSet {
+                id: ObjectId::new(),
+                questions: (0..3)
+                    .map(|_| ExamEnvironmentMultipleChoiceQuestion {
+                        id: ObjectId::new(),
+                        text: "question".to_string(),
+                        tags: vec!["a".to_string()],
+                        answers: [true, false, false]
+                            .iter()
+                            .enumerate()
+                            .map(|(i, is_correct)| ExamEnvironmentAnswer {
+                                id: ObjectId::new(),
+                                is_correct: *is_correct,
+                                text: format!("answer {i}"),
+                            })
+                            .collect(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        ExamEnvironmentExam {
+            id: ObjectId::new(),
+            question_sets,
+            config: ExamEnvironmentConfig {
+                name: "exam".to_string(),
+                question_sets: vec![ExamEnvironmentQuestionSetConfig {
+                    number_of_set: 1,
+                    number_of_questions: 2,
+                    number_of_correct_answers: 1,
+                    number_of_incorrect_answers: 1,
+                    ..Default::default()
+                }],
+                tags: vec![ExamEnvironmentTagConfig {
+                    group: vec!["a".to_string()],
+                    number_of_questions: 2,
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn generation_against_exam() {
+        let mut exam = exam();
+        let generation = generate_exam(ExamInput {
+            id: exam.id,
+            question_sets: exam.question_sets.clone(),
+            config: exam.config.clone(),
+        })
+        .unwrap();
+        assert!(validate_generation_against_exam(&exam, &generation).is_ok());
+
+        // Too many incorrect answers
+        let mut invalid = generation.clone();
+        let question_id = invalid.question_sets[0].questions[0].id;
+        let question = exam
+            .question_sets
+            .iter()
+            .flat_map(|qs| qs.questions.iter())
+            .find(|q| q.id == question_id)
+            .unwrap();
+        invalid.question_sets[0].questions[0].answers =
+            question.answers.iter().map(|a| a.id).collect();
+        assert!(validate_generation_against_exam(&exam, &invalid).is_err());
+
+        // Extra question set
+        let mut invalid = generation.clone();
+        let unused_set = exam
+            .question_sets
+            .iter()
+            .find(|qs| qs.id != generation.question_sets[0].id)
+            .unwrap();
+        invalid
+            .question_sets
+            .push(ExamEnvironmentGeneratedQuestionSet {
+                id: unused_set.id,
+                questions: unused_set.questions[..2]
+                    .iter()
+                    .map(|q| ExamEnvironmentGeneratedMultipleChoiceQuestion {
+                        id: q.id,
+                        answers: q.answers[..2].iter().map(|a| a.id).collect(),
+                    })
+                    .collect(),
+            });
+        let err = validate_generation_against_exam(&exam, &invalid).unwrap_err();
+        assert!(err.to_string().contains("config expects 1"));
+
+        // Tag config no longer met
+        exam.config.tags[0].number_of_questions = 3;
+        assert!(validate_generation_against_exam(&exam, &generation).is_err());
+        exam.config.tags[0].number_of_questions = 2;
+
+        // Deprecated question
+        for question in exam
+            .question_sets
+            .iter_mut()
+            .flat_map(|qs| qs.questions.iter_mut())
+        {
+            if question.id == question_id {
+                question.deprecated = true;
+            }
+        }
+        let err = validate_generation_against_exam(&exam, &generation).unwrap_err();
+        assert!(err.to_string().contains("deprecated"));
+    }
+
+    #[test]
+    fn try_generate_validates() {
+        let mut exam = exam();
+        let generation = try_generate(exam.clone(), None).unwrap();
+        assert!(validate_generation_against_exam(&exam, &generation.generated_exam).is_ok());
+        assert_eq!(generation.expected_score, None);
+
+        // The expected score is reported when difficulty estimates are given
+        let options = DifficultyOptions {
+            difficulties: exam
+                .question_sets
+                .iter()
+                .flat_map(|qs| qs.questions.iter().enumerate())
+                .map(|(i, q)| QuestionDifficulty {
+                    question_id: q.id,
+                    difficulty: if i % 2 == 0 { 0.8 } else { 0.4 },
+                })
+                .collect(),
+            min_expected_score: 0.0,
+            max_expected_score: 100.0,
+            seed: Some(1),
+        };
+        let generation = try_generate(exam.clone(), Some(&options)).unwrap();
+        assert!(validate_generation_against_exam(&exam, &generation.generated_exam).is_ok());
+        let expected_score = get_expected_score(&generation.generated_exam, &options.difficulties);
+        assert_eq!(generation.expected_score, Some(expected_score));
+
+        // Only 6 questions are tagged "a"
+        exam.config.tags[0].number_of_questions = 7;
+        let err = try_generate(exam, None).unwrap_err();
+        assert!(matches!(err, Error::InvalidConfig(_)));
+    }
+}
@@ -4,10 +4,24 @@
 //!
 //! - Calculate attempt score
 //! - Validate exam config
+//! - Check exam config feasibility
 //! - Generate exams
+//! - Generate batches of exams with balanced exposure
+//! - Estimate generation failure rate and question exposure
+//! - Generate exams within a target difficulty band
 //! - Validate generated exams
+//! - Detect collusion between attempts
+//! - Build population timing baselines
+//! - Analyse question and answer statistics
+//! - Estimate exam and generation reliability
 //!
+pub mod analytics;
 pub mod attempt;
+pub mod baseline;
+pub mod collusion;
+pub mod difficulty;
 pub mod error;
+pub mod exposure;
 pub mod generation;
 pub mod misc;
+pub mod solver;
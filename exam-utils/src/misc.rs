@@ -1,11 +1,18 @@
 use mongodb::bson::oid::ObjectId;
 use prisma;
-use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
-use std::time::{Duration, Instant};
+use std::collections::BTreeMap;
 use tracing::trace;
 
-use crate::error::Error;
+use crate::{
+    error::Error,
+    exposure::ExposureCounts,
+    solver::{
+        ConfigConstraint, Feasibility, QuestionSetAllocation, get_feasibility, solve_with_rng,
+    },
+};
 
 /// Calculates the attempt score, and compares score >= pass_score
 pub fn check_attempt_pass(
@@ -13,90 +20,434 @@ pub fn check_attempt_pass(
     generated_exam: &prisma::ExamEnvironmentGeneratedExam,
     attempt: &prisma::ExamEnvironmentExamAttempt,
 ) -> bool {
-    let passing_percent = exam.config.passing_percent;
-    if let Ok(score) = calculate_score(exam, generated_exam, attempt) {
-        return score >= passing_percent;
+    match calculate_score(exam, generated_exam, attempt) {
+        Ok(score_report) => score_report.passed,
+        Err(e) => {
+            tracing::warn!(attempt = %attempt.id, error = %e, "unable to calculate score");
+            false
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreReport {
+    /// Number of generated questions answered fully correctly
+    pub correct: usize,
+    /// Number of questions in the generation
+    pub total: usize,
+    /// Sum of each question's weighted credit
+    pub score: f64,
+    /// Sum of each question's weight
+    #[serde(rename = "maxScore")]
+    pub max_score: f64,
+    /// `score / max_score * 100`
+    pub percentage: f64,
+    #[serde(rename = "passingPercent")]
+    pub passing_percent: f64,
+    /// Whether `percentage >= passing_percent`
+    pub passed: bool,
+    #[serde(rename = "questionSets")]
+    pub question_sets: Vec<QuestionSetScore>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionSetScore {
+    pub id: ObjectId,
+    pub correct: usize,
+    pub total: usize,
+    pub score: f64,
+    #[serde(rename = "maxScore")]
+    pub max_score: f64,
+    pub questions: Vec<QuestionScore>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionScore {
+    pub id: ObjectId,
+    pub answered: bool,
+    /// Whether the answers exactly match the correct generated answers
+    #[serde(rename = "isCorrect")]
+    pub is_correct: bool,
+    /// 0.0 -> 1.0 credit given by the `CreditPolicy`
+    pub credit: f64,
+    pub weight: f64,
+}
+
+/// How answers are scored. The default is the all-or-nothing, equally weighted scoring.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScoringPolicy {
+    #[serde(default)]
+    pub credit: CreditPolicy,
+    /// Weight of every question in a question set. Question sets not listed have a weight of 1.0.
+    #[serde(rename = "questionSetWeights", default)]
+    pub question_set_weights: Vec<QuestionSetWeight>,
+    /// Weight of questions with a tag. Questions with several weighted tags use the largest weight.
+    /// Questions without a weighted tag have a weight of 1.0.
+    #[serde(rename = "tagWeights", default)]
+    pub tag_weights: Vec<TagWeight>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionSetWeight {
+    pub id: ObjectId,
+    pub weight: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TagWeight {
+    pub tag: String,
+    pub weight: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CreditPolicy {
+    /// 1.0 if exactly the correct generated answers are selected, otherwise 0.0
+    #[default]
+    AllOrNothing,
+    /// `(correct selected - incorrect selected) / correct generated`, floored at 0.0
+    CorrectMinusIncorrect,
+    /// Fraction of generated answers correctly selected or correctly left unselected
+    Proportional,
+}
+
+impl ScoringPolicy {
+    /// Weight of a question: its question set weight multiplied by its tag weight
+    pub fn question_weight(&self, question_set_id: &ObjectId, tags: &[String]) -> f64 {
+        let question_set_weight = self
+            .question_set_weights
+            .iter()
+            .find(|w| &w.id == question_set_id)
+            .map(|w| w.weight)
+            .unwrap_or(1.0);
+        let tag_weight = self
+            .tag_weights
+            .iter()
+            .filter(|w| tags.contains(&w.tag))
+            .map(|w| w.weight)
+            .reduce(f64::max)
+            .unwrap_or(1.0);
+
+        question_set_weight * tag_weight
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let weights = self
+            .question_set_weights
+            .iter()
+            .map(|w| (w.id.to_hex(), w.weight))
+            .chain(self.tag_weights.iter().map(|w| (w.tag.clone(), w.weight)));
+        for (name, weight) in weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(Error::Score(format!(
+                    "{name} weight must be a finite, non-negative number: {weight}"
+                )));
+            }
+        }
+        Ok(())
     }
-    false
 }
 
+/// Scores the attempt against the questions in the generated exam, using the default `ScoringPolicy`.
+///
+/// Errors if the attempt contains questions not in the exam or generation,
+/// or if the generation contains questions not in the exam.
 pub fn calculate_score(
     exam: &prisma::ExamEnvironmentExam,
     generated_exam: &prisma::ExamEnvironmentGeneratedExam,
     attempt: &prisma::ExamEnvironmentExamAttempt,
-) -> Result<f64, String> {
-    let attempt_question_sets = &attempt.question_sets;
-    let generated_question_sets = &generated_exam.question_sets;
+) -> Result<ScoreReport, Error> {
+    calculate_score_with_policy(exam, generated_exam, attempt, &ScoringPolicy::default())
+}
 
-    let total_questions: usize = generated_question_sets
-        .iter()
-        .map(|qs| qs.questions.len())
-        .sum();
-    let mut correct_questions: usize = 0;
+/// Scores the attempt against the questions in the generated exam, using the given `ScoringPolicy`.
+pub fn calculate_score_with_policy(
+    exam: &prisma::ExamEnvironmentExam,
+    generated_exam: &prisma::ExamEnvironmentGeneratedExam,
+    attempt: &prisma::ExamEnvironmentExamAttempt,
+    policy: &ScoringPolicy,
+) -> Result<ScoreReport, Error> {
+    policy.validate()?;
 
-    for attempt_question_set in attempt_question_sets {
-        let exam_question_set = exam
+    // Every attempted question must have been generated
+    for attempt_question_set in &attempt.question_sets {
+        let generated_question_set = generated_exam
             .question_sets
             .iter()
-            .find(|qs| &qs.id == &attempt_question_set.id)
+            .find(|qs| qs.id == attempt_question_set.id)
             .ok_or_else(|| {
-                format!(
-                    "Attempt question set {} must exist in exam {}",
-                    attempt_question_set.id, exam.id
-                )
+                Error::Score(format!(
+                    "Generated question set {} must exist in generated exam {}",
+                    attempt_question_set.id, generated_exam.id
+                ))
             })?;
 
-        let generated_question_set = generated_question_sets
+        for attempt_question in &attempt_question_set.questions {
+            if !generated_question_set
+                .questions
+                .iter()
+                .any(|q| q.id == attempt_question.id)
+            {
+                return Err(Error::Score(format!(
+                    "Generated question {} must exist in generated exam {}",
+                    attempt_question.id, generated_exam.id
+                )));
+            }
+        }
+    }
+
+    let mut question_sets = vec![];
+    for generated_question_set in &generated_exam.question_sets {
+        let exam_question_set = exam
+            .question_sets
             .iter()
-            .find(|qs| &qs.id == &attempt_question_set.id)
+            .find(|qs| qs.id == generated_question_set.id)
             .ok_or_else(|| {
-                format!(
-                    "Generated question set {} must exist in generated exam {}",
-                    attempt_question_set.id, generated_exam.id
-                )
+                Error::Score(format!(
+                    "Generated question set {} must exist in exam {}",
+                    generated_question_set.id, exam.id
+                ))
             })?;
+        let attempt_question_set = attempt
+            .question_sets
+            .iter()
+            .find(|qs| qs.id == generated_question_set.id);
 
-        for attempt_question in &attempt_question_set.questions {
+        let mut questions = vec![];
+        for generated_question in &generated_question_set.questions {
             let exam_question = exam_question_set
                 .questions
                 .iter()
-                .find(|q| &q.id == &attempt_question.id)
+                .find(|q| q.id == generated_question.id)
                 .ok_or_else(|| {
-                    format!(
-                        "Attempt question {} must exist in exam {}",
-                        attempt_question.id, exam.id
-                    )
+                    Error::Score(format!(
+                        "Generated question {} must exist in exam {}",
+                        generated_question.id, exam.id
+                    ))
                 })?;
+            let attempt_question = attempt_question_set
+                .and_then(|qs| qs.questions.iter().find(|q| q.id == generated_question.id));
+
+            let (is_correct, credit) = match attempt_question {
+                Some(aq) => (
+                    compare_answers(
+                        &exam_question.answers,
+                        &generated_question.answers,
+                        &aq.answers,
+                    ),
+                    get_answer_credit(
+                        &exam_question.answers,
+                        &generated_question.answers,
+                        &aq.answers,
+                        &policy.credit,
+                    ),
+                ),
+                None => (false, 0.0),
+            };
+
+            questions.push(QuestionScore {
+                id: generated_question.id,
+                answered: attempt_question.is_some(),
+                is_correct,
+                credit,
+                weight: policy.question_weight(&generated_question_set.id, &exam_question.tags),
+            });
+        }
+
+        question_sets.push(QuestionSetScore {
+            id: generated_question_set.id,
+            correct: questions.iter().filter(|q| q.is_correct).count(),
+            total: questions.len(),
+            score: questions.iter().map(|q| q.credit * q.weight).sum(),
+            max_score: questions.iter().map(|q| q.weight).sum(),
+            questions,
+        });
+    }
+
+    let correct: usize = question_sets.iter().map(|qs| qs.correct).sum();
+    let total: usize = question_sets.iter().map(|qs| qs.total).sum();
+    let score: f64 = question_sets.iter().map(|qs| qs.score).sum();
+    let max_score: f64 = question_sets.iter().map(|qs| qs.max_score).sum();
+    if total == 0 {
+        return Err(Error::Score(format!(
+            "Generated exam {} has no questions",
+            generated_exam.id
+        )));
+    }
+    if max_score <= 0.0 {
+        return Err(Error::Score(format!(
+            "Generated exam {} has no weighted questions",
+            generated_exam.id
+        )));
+    }
+
+    let percentage = (score / max_score) * 100.0;
+    let passing_percent = exam.config.passing_percent;
+
+    Ok(ScoreReport {
+        correct,
+        total,
+        score,
+        max_score,
+        percentage,
+        passing_percent,
+        passed: percentage >= passing_percent,
+        question_sets,
+    })
+}
+
+/// 0.0 -> 1.0 credit for the attempt answers of a question
+pub fn get_answer_credit(
+    exam_answers: &[prisma::ExamEnvironmentAnswer],
+    generated_answers: &[ObjectId],
+    attempt_answers: &[ObjectId],
+    credit_policy: &CreditPolicy,
+) -> f64 {
+    let is_correct = |id: &ObjectId| exam_answers.iter().any(|a| a.is_correct && &a.id == id);
+
+    match credit_policy {
+        CreditPolicy::AllOrNothing => {
+            if compare_answers(exam_answers, generated_answers, attempt_answers) {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        CreditPolicy::CorrectMinusIncorrect => {
+            let correct_generated = generated_answers.iter().filter(|a| is_correct(a)).count();
+            if correct_generated == 0 {
+                return 0.0;
+            }
+            let correct_selected = attempt_answers
+                .iter()
+                .filter(|a| generated_answers.contains(a) && is_correct(a))
+                .count();
+            let incorrect_selected = attempt_answers.len() - correct_selected;
+            (correct_selected as f64 - incorrect_selected as f64).max(0.0)
+                / correct_generated as f64
+        }
+        CreditPolicy::Proportional => {
+            if generated_answers.is_empty() {
+                return 0.0;
+            }
+            let correctly_classified = generated_answers
+                .iter()
+                .filter(|a| is_correct(a) == attempt_answers.contains(a))
+                .count();
+            correctly_classified as f64 / generated_answers.len() as f64
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TagBreakdown {
+    /// Results for each tag of the generated questions, ordered by tag
+    pub tags: Vec<TagScore>,
+    /// Results for each `config.tags` group, in config order
+    pub groups: Vec<TagGroupScore>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TagScore {
+    pub tag: String,
+    #[serde(flatten)]
+    pub result: TagResult,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TagGroupScore {
+    pub group: Vec<String>,
+    #[serde(flatten)]
+    pub result: TagResult,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TagResult {
+    /// Number of generated questions
+    pub total: usize,
+    pub answered: usize,
+    pub correct: usize,
+    /// `correct / total * 100`
+    pub percentage: f64,
+}
+
+impl TagResult {
+    fn add(&mut self, question: &QuestionScore) {
+        self.total += 1;
+        if question.answered {
+            self.answered += 1;
+        }
+        if question.is_correct {
+            self.correct += 1;
+        }
+        self.percentage = (self.correct as f64 / self.total as f64) * 100.0;
+    }
+}
+
+/// Breaks the attempt score down by question tag, and by `config.tags` group.
+///
+/// A question counts towards a group if it has all of the group's tags.
+pub fn get_tag_breakdown(
+    exam: &prisma::ExamEnvironmentExam,
+    generated_exam: &prisma::ExamEnvironmentGeneratedExam,
+    attempt: &prisma::ExamEnvironmentExamAttempt,
+) -> Result<TagBreakdown, Error> {
+    let score_report = calculate_score(exam, generated_exam, attempt)?;
+
+    let mut tags: BTreeMap<&str, TagResult> = BTreeMap::new();
+    let mut groups: Vec<TagGroupScore> = exam
+        .config
+        .tags
+        .iter()
+        .map(|tag_config| TagGroupScore {
+            group: tag_config.group.clone(),
+            result: TagResult::default(),
+        })
+        .collect();
+
+    for question_set_score in &score_report.question_sets {
+        // Existence is checked in `calculate_score`
+        let Some(exam_question_set) = exam
+            .question_sets
+            .iter()
+            .find(|qs| qs.id == question_set_score.id)
+        else {
+            continue;
+        };
 
-            let generated_question = generated_question_set
+        for question_score in &question_set_score.questions {
+            let Some(exam_question) = exam_question_set
                 .questions
                 .iter()
-                .find(|q| &q.id == &attempt_question.id)
-                .ok_or_else(|| {
-                    format!(
-                        "Generated question {} must exist in generated exam {}",
-                        attempt_question.id, generated_exam.id
-                    )
-                })?;
+                .find(|q| q.id == question_score.id)
+            else {
+                continue;
+            };
 
-            if compare_answers(
-                &exam_question.answers,
-                &generated_question.answers,
-                &attempt_question.answers,
-            ) {
-                let correct_exam_answer_ids: Vec<ObjectId> = exam_question
-                    .answers
+            for tag in &exam_question.tags {
+                tags.entry(tag).or_default().add(question_score);
+            }
+            for group_score in groups.iter_mut() {
+                if group_score
+                    .group
                     .iter()
-                    .filter_map(|a| if a.is_correct { Some(a.id) } else { None })
-                    .collect();
-                let selected_answer_ids = attempt_question.answers.clone();
-                assert!(correct_exam_answer_ids.contains(selected_answer_ids.get(0).unwrap()));
-                correct_questions += 1;
+                    .all(|t| exam_question.tags.contains(t))
+                {
+                    group_score.result.add(question_score);
+                }
             }
         }
     }
 
-    Ok((correct_questions as f64 / total_questions as f64) * 100.0)
+    Ok(TagBreakdown {
+        tags: tags
+            .into_iter()
+            .map(|(tag, result)| TagScore {
+                tag: tag.to_string(),
+                result,
+            })
+            .collect(),
+        groups,
+    })
 }
 
 pub fn compare_answers(
@@ -120,34 +471,374 @@ pub fn compare_answers(
     answers_equal && correct_generated_answers.len() == attempt_answers.len()
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    pub severity: ConfigIssueSeverity,
+    pub code: ConfigIssueCode,
+    pub message: String,
+    #[serde(rename = "questionSetId")]
+    pub question_set_id: Option<ObjectId>,
+    #[serde(rename = "questionId")]
+    pub question_id: Option<ObjectId>,
+    #[serde(rename = "answerId")]
+    pub answer_id: Option<ObjectId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConfigIssueSeverity {
+    /// The exam cannot be generated or taken as configured
+    Error,
+    /// The exam can be generated, but likely needs fixing
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConfigIssueCode {
+    EmptyName,
+    InvalidPassingPercent,
+    NotEnoughTagQuestions,
+    NotEnoughQuestionSets,
+    NotEnoughQuestions,
+    NoQuestionSetLargeEnough,
+    NotEnoughCorrectAnswers,
+    NotEnoughIncorrectAnswers,
+    EmptyQuestionText,
+    NoCorrectAnswer,
+    EmptyAnswerText,
+    DuplicateAnswerText,
+    /// The config is only satisfiable when counting deprecated questions, which are never generated
+    DeprecatedQuestionsExhaustPool,
+    /// Deprecated questions reduce the questions available to a config, which is still satisfiable without them
+    DeprecatedQuestionsShrinkPool,
+    QuestionWithoutTags,
+    /// No generation satisfies all question set and tag configs together
+    Infeasible,
+}
+
+impl ConfigIssue {
+    fn error(code: ConfigIssueCode, message: String) -> Self {
+        Self {
+            severity: ConfigIssueSeverity::Error,
+            code,
+            message,
+            question_set_id: None,
+            question_id: None,
+            answer_id: None,
+        }
+    }
+
+    fn warning(code: ConfigIssueCode, message: String) -> Self {
+        Self {
+            severity: ConfigIssueSeverity::Warning,
+            ..Self::error(code, message)
+        }
+    }
+
+    fn with_question_set(mut self, question_set_id: ObjectId) -> Self {
+        self.question_set_id = Some(question_set_id);
+        self
+    }
+
+    fn with_question(mut self, question_id: ObjectId) -> Self {
+        self.question_id = Some(question_id);
+        self
+    }
+
+    fn with_answer(mut self, answer_id: ObjectId) -> Self {
+        self.answer_id = Some(answer_id);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == ConfigIssueSeverity::Error
+    }
+}
+
 /// Validate Exam Config:
 /// - `config.name` is not empty
 /// - `config.passing_percent` is between 0 and 100
-/// - `config.tags` is solvable
-/// - `config.question_sets` is solvable
+/// - `config.tags` is solvable, without deprecated questions
+/// - `config.question_sets` is solvable, without deprecated questions
 /// - `question_sets.questions.text` is not empty
 /// - `question_sets.questions.answers` has at least one correct answer
 /// - `question_sets.questions.answers.text` is not empty
 ///
-/// A "solvable" config means that there are enough sets, questions, and answers to satisfy the constraints
-pub fn validate_config(exam: &prisma::ExamEnvironmentExam) -> Result<(), String> {
+/// Warns about:
+/// - deprecated questions shrinking the questions available to `config.tags` or `config.question_sets`
+/// - duplicate answer text within a question
+/// - questions without tags
+///
+/// A "solvable" config means that there are enough sets, questions, and answers to satisfy the constraints.
+/// If each constraint is solvable on its own, the constraints are also checked together with `solver::check_feasibility`.
+/// All issues are returned, errors first. The config is valid if there are no errors.
+pub fn validate_config(exam: &prisma::ExamEnvironmentExam) -> Vec<ConfigIssue> {
+    validate_config_with(exam, || get_feasibility(&exam.question_sets, &exam.config)).0
+}
+
+/// Validates the config as `validate_config`, checking the constraints together with `solve`.
+///
+/// Also returns the allocations found by `solve`, if it was run and a generation exists.
+pub(crate) fn validate_config_with(
+    exam: &prisma::ExamEnvironmentExam,
+    solve: impl FnOnce() -> Feasibility,
+) -> (Vec<ConfigIssue>, Option<Vec<QuestionSetAllocation>>) {
     let config = &exam.config;
     let question_sets = &exam.question_sets;
+    let mut issues = vec![];
 
     if config.name.is_empty() {
-        return Err("Config name is empty".into());
+        issues.push(ConfigIssue::error(
+            ConfigIssueCode::EmptyName,
+            "Config name is empty".into(),
+        ));
     }
 
     if config.passing_percent < 0.0 || config.passing_percent > 100.0 {
-        return Err("Config passing percent must be between 0.0 and 100.0".into());
+        issues.push(ConfigIssue::error(
+            ConfigIssueCode::InvalidPassingPercent,
+            "Config passing percent must be between 0.0 and 100.0".into(),
+        ));
+    }
+
+    let pool_issues = get_pool_issues(config, question_sets, true);
+    // Deprecated questions are never generated, so the pool must also be sufficient without them
+    let mut exhausted_tag_configs = vec![];
+    let mut exhausted_question_set_configs = vec![];
+    for ((code, i), issue) in get_pool_issues(config, question_sets, false) {
+        if pool_issues.iter().any(|((c, j), _)| *c == code && *j == i) {
+            continue;
+        }
+        if code == ConfigIssueCode::NotEnoughTagQuestions {
+            exhausted_tag_configs.push(i);
+        } else {
+            exhausted_question_set_configs.push(i);
+        }
+        issues.push(ConfigIssue {
+            severity: ConfigIssueSeverity::Error,
+            code: ConfigIssueCode::DeprecatedQuestionsExhaustPool,
+            message: format!("{} (excluding deprecated questions)", issue.message),
+            ..issue
+        });
+    }
+    issues.extend(pool_issues.into_iter().map(|(_, issue)| issue));
+
+    let (tag_pools, question_set_pools) = get_pool_sizes(config, question_sets, true);
+    let (usable_tag_pools, usable_question_set_pools) =
+        get_pool_sizes(config, question_sets, false);
+    for (i, tag_config) in config.tags.iter().enumerate() {
+        if usable_tag_pools[i] < tag_pools[i] && !exhausted_tag_configs.contains(&i) {
+            issues.push(ConfigIssue::warning(
+                ConfigIssueCode::DeprecatedQuestionsShrinkPool,
+                format!(
+                    "Deprecated questions shrink the pool for tag config: {:?}. Available: {}, Including deprecated: {}",
+                    tag_config, usable_tag_pools[i], tag_pools[i]
+                ),
+            ));
+        }
+    }
+    for (i, qs_config) in config.question_sets.iter().enumerate() {
+        if usable_question_set_pools[i] < question_set_pools[i]
+            && !exhausted_question_set_configs.contains(&i)
+        {
+            issues.push(ConfigIssue::warning(
+                ConfigIssueCode::DeprecatedQuestionsShrinkPool,
+                format!(
+                    "Deprecated questions shrink the pool for question set config: {:?}. Available: {}, Including deprecated: {}",
+                    qs_config, usable_question_set_pools[i], question_set_pools[i]
+                ),
+            ));
+        }
+    }
+
+    // For each `config.question_sets.number_of_correct_answers` and `number_of_incorrect_answers`, ensure there are enough answers in the question sets of that type
+    for qs_config in &config.question_sets {
+        for question_set in question_sets
+            .iter()
+            .filter(|qs| qs._type == qs_config._type)
+        {
+            for question in &question_set.questions {
+                let num_correct_answers = question.answers.iter().filter(|a| a.is_correct).count();
+                let num_incorrect_answers =
+                    question.answers.iter().filter(|a| !a.is_correct).count();
+                if num_correct_answers < qs_config.number_of_correct_answers as usize {
+                    issues.push(
+                        ConfigIssue::error(
+                            ConfigIssueCode::NotEnoughCorrectAnswers,
+                            format!(
+                                "Not enough correct answers for question {:?} in question set {:?}. Available: {}, Required: {}",
+                                question.id,
+                                question_set.id,
+                                num_correct_answers,
+                                qs_config.number_of_correct_answers
+                            ),
+                        )
+                        .with_question_set(question_set.id)
+                        .with_question(question.id),
+                    );
+                }
+                if num_incorrect_answers < qs_config.number_of_incorrect_answers as usize {
+                    issues.push(
+                        ConfigIssue::error(
+                            ConfigIssueCode::NotEnoughIncorrectAnswers,
+                            format!(
+                                "Not enough incorrect answers for question {:?} in question set {:?}. Available: {}, Required: {}",
+                                question.id,
+                                question_set.id,
+                                num_incorrect_answers,
+                                qs_config.number_of_incorrect_answers
+                            ),
+                        )
+                        .with_question_set(question_set.id)
+                        .with_question(question.id),
+                    );
+                }
+            }
+        }
+    }
+
+    for qs in question_sets {
+        for question in &qs.questions {
+            if question.text.trim().is_empty() {
+                issues.push(
+                    ConfigIssue::error(
+                        ConfigIssueCode::EmptyQuestionText,
+                        format!("Question {:?} has empty text", question.id),
+                    )
+                    .with_question_set(qs.id)
+                    .with_question(question.id),
+                );
+            }
+            let has_correct_answer = question.answers.iter().any(|a| a.is_correct);
+            if !has_correct_answer {
+                issues.push(
+                    ConfigIssue::error(
+                        ConfigIssueCode::NoCorrectAnswer,
+                        format!("Question {:?} has no correct answers", question.id),
+                    )
+                    .with_question_set(qs.id)
+                    .with_question(question.id),
+                );
+            }
+            if question.tags.is_empty() {
+                issues.push(
+                    ConfigIssue::warning(
+                        ConfigIssueCode::QuestionWithoutTags,
+                        format!("Question {:?} has no tags", question.id),
+                    )
+                    .with_question_set(qs.id)
+                    .with_question(question.id),
+                );
+            }
+            for (i, answer) in question.answers.iter().enumerate() {
+                if answer.text.trim().is_empty() {
+                    issues.push(
+                        ConfigIssue::error(
+                            ConfigIssueCode::EmptyAnswerText,
+                            format!(
+                                "Answer {:?} in question {:?} has empty text",
+                                answer.id, question.id
+                            ),
+                        )
+                        .with_question_set(qs.id)
+                        .with_question(question.id)
+                        .with_answer(answer.id),
+                    );
+                } else if question.answers[..i]
+                    .iter()
+                    .any(|a| a.text.trim() == answer.text.trim())
+                {
+                    issues.push(
+                        ConfigIssue::warning(
+                            ConfigIssueCode::DuplicateAnswerText,
+                            format!(
+                                "Answer {:?} in question {:?} has the same text as another answer",
+                                answer.id, question.id
+                            ),
+                        )
+                        .with_question_set(qs.id)
+                        .with_question(question.id)
+                        .with_answer(answer.id),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut allocations = None;
+    if !issues.iter().any(|issue| issue.is_error()) {
+        match solve() {
+            Feasibility::Feasible(feasible) => allocations = Some(feasible),
+            Feasibility::Infeasible(constraints) => {
+                let constraints: Vec<String> = constraints.iter().map(|c| c.to_string()).collect();
+                issues.push(ConfigIssue::error(
+                    ConfigIssueCode::Infeasible,
+                    format!(
+                        "No generation satisfies these constraints together: {}",
+                        constraints.join("; ")
+                    ),
+                ));
+            }
+        }
     }
 
+    issues.sort_by_key(|issue| !issue.is_error());
+    (issues, allocations)
+}
+
+/// Number of questions available to each of `config.tags`, and to each of `config.question_sets`.
+fn get_pool_sizes(
+    config: &prisma::ExamEnvironmentConfig,
+    question_sets: &[prisma::ExamEnvironmentQuestionSet],
+    include_deprecated: bool,
+) -> (Vec<usize>, Vec<usize>) {
+    let usable = |question: &&prisma::ExamEnvironmentMultipleChoiceQuestion| {
+        include_deprecated || !question.deprecated
+    };
+
+    let tag_pools = config
+        .tags
+        .iter()
+        .map(|tag_config| {
+            question_sets
+                .iter()
+                .flat_map(|qs| qs.questions.iter().filter(usable))
+                .filter(|q| tag_config.group.iter().all(|tag| q.tags.contains(tag)))
+                .count()
+        })
+        .collect();
+    let question_set_pools = config
+        .question_sets
+        .iter()
+        .map(|qs_config| {
+            question_sets
+                .iter()
+                .filter(|qs| qs._type == qs_config._type)
+                .flat_map(|qs| qs.questions.iter().filter(usable))
+                .count()
+        })
+        .collect();
+    (tag_pools, question_set_pools)
+}
+
+/// Issues with the number of questions and question sets available to satisfy `config.tags` and `config.question_sets`.
+///
+/// Each issue is keyed by its code and the index of the config it applies to.
+fn get_pool_issues(
+    config: &prisma::ExamEnvironmentConfig,
+    question_sets: &[prisma::ExamEnvironmentQuestionSet],
+    include_deprecated: bool,
+) -> Vec<((ConfigIssueCode, usize), ConfigIssue)> {
+    let mut issues = vec![];
+    let usable = |question: &&prisma::ExamEnvironmentMultipleChoiceQuestion| {
+        include_deprecated || !question.deprecated
+    };
+
     // For each tag config, generate a map of (tag config, number of questions satisfying tag)
     // If any tag config `number_of_questions` > available questions with that tag, return error
-    for tag_config in &config.tags {
+    for (i, tag_config) in config.tags.iter().enumerate() {
         let mut available_questions = 0;
         for question_set in question_sets {
-            for question in &question_set.questions {
+            for question in question_set.questions.iter().filter(usable) {
                 let group = &tag_config.group;
                 // if `question.tags` includes all of `group`, then it satisfies the tag config
                 if group.iter().all(|tag| question.tags.contains(tag)) {
@@ -156,23 +847,35 @@ pub fn validate_config(exam: &prisma::ExamEnvironmentExam) -> Result<(), String>
             }
         }
         if available_questions < tag_config.number_of_questions as usize {
-            return Err(format!(
-                "Not enough questions for tag config: {:?}. Available: {}, Required: {}",
-                tag_config, available_questions, tag_config.number_of_questions
+            issues.push((
+                (ConfigIssueCode::NotEnoughTagQuestions, i),
+                ConfigIssue::error(
+                    ConfigIssueCode::NotEnoughTagQuestions,
+                    format!(
+                        "Not enough questions for tag config: {:?}. Available: {}, Required: {}",
+                        tag_config, available_questions, tag_config.number_of_questions
+                    ),
+                ),
             ));
         }
     }
 
     // For each question set config, ensure there are enough question sets of that type
-    for qs_config in &config.question_sets {
+    for (i, qs_config) in config.question_sets.iter().enumerate() {
         let available_question_sets = question_sets
             .iter()
             .filter(|qs| qs._type == qs_config._type)
             .count();
         if available_question_sets < qs_config.number_of_set as usize {
-            return Err(format!(
-                "Not enough question sets for question set config: {:?}. Available: {}, Required: {}",
-                qs_config, available_question_sets, qs_config.number_of_set
+            issues.push((
+                (ConfigIssueCode::NotEnoughQuestionSets, i),
+                ConfigIssue::error(
+                    ConfigIssueCode::NotEnoughQuestionSets,
+                    format!(
+                        "Not enough question sets for question set config: {:?}. Available: {}, Required: {}",
+                        qs_config, available_question_sets, qs_config.number_of_set
+                    ),
+                ),
             ));
         }
     }
@@ -180,14 +883,14 @@ pub fn validate_config(exam: &prisma::ExamEnvironmentExam) -> Result<(), String>
     // For each `config.question_sets.number_of_questions`, ensure there are enough questions in the question sets of that type
     // Tally the total number of questions for a given type
     // Also, ensure for each question_set config, there exists a question set of that type with enough questions
-    for qs_config in &config.question_sets {
+    for (i, qs_config) in config.question_sets.iter().enumerate() {
         let mut total_questions = 0;
         let mut has_enough_in_single_set = false;
         for question_set in question_sets
             .iter()
             .filter(|qs| qs._type == qs_config._type)
         {
-            let num_questions_in_set = question_set.questions.len();
+            let num_questions_in_set = question_set.questions.iter().filter(usable).count();
             total_questions += num_questions_in_set;
             if num_questions_in_set >= qs_config.number_of_questions as usize {
                 has_enough_in_single_set = true;
@@ -196,74 +899,34 @@ pub fn validate_config(exam: &prisma::ExamEnvironmentExam) -> Result<(), String>
         if total_questions
             < qs_config.number_of_set as usize * qs_config.number_of_questions as usize
         {
-            return Err(format!(
-                "Not enough questions overall for question set config: {:?}. Available: {}, Required: {}",
-                qs_config,
-                total_questions,
-                qs_config.number_of_set * qs_config.number_of_questions
+            issues.push((
+                (ConfigIssueCode::NotEnoughQuestions, i),
+                ConfigIssue::error(
+                    ConfigIssueCode::NotEnoughQuestions,
+                    format!(
+                        "Not enough questions overall for question set config: {:?}. Available: {}, Required: {}",
+                        qs_config,
+                        total_questions,
+                        qs_config.number_of_set * qs_config.number_of_questions
+                    ),
+                ),
             ));
         }
         if !has_enough_in_single_set {
-            return Err(format!(
-                "No single question set has enough questions for question set config: {:?}",
-                qs_config
+            issues.push((
+                (ConfigIssueCode::NoQuestionSetLargeEnough, i),
+                ConfigIssue::error(
+                    ConfigIssueCode::NoQuestionSetLargeEnough,
+                    format!(
+                        "No single question set has enough questions for question set config: {:?}",
+                        qs_config
+                    ),
+                ),
             ));
         }
     }
 
-    // For each `config.question_sets.number_of_correct_answers` and `number_of_incorrect_answers`, ensure there are enough answers in the question sets of that type
-    for qs_config in &config.question_sets {
-        for question_set in question_sets
-            .iter()
-            .filter(|qs| qs._type == qs_config._type)
-        {
-            for question in &question_set.questions {
-                let num_correct_answers = question.answers.iter().filter(|a| a.is_correct).count();
-                let num_incorrect_answers =
-                    question.answers.iter().filter(|a| !a.is_correct).count();
-                if num_correct_answers < qs_config.number_of_correct_answers as usize {
-                    return Err(format!(
-                        "Not enough correct answers for question {:?} in question set {:?}. Available: {}, Required: {}",
-                        question.id,
-                        question_set.id,
-                        num_correct_answers,
-                        qs_config.number_of_correct_answers
-                    ));
-                }
-                if num_incorrect_answers < qs_config.number_of_incorrect_answers as usize {
-                    return Err(format!(
-                        "Not enough incorrect answers for question {:?} in question set {:?}. Available: {}, Required: {}",
-                        question.id,
-                        question_set.id,
-                        num_incorrect_answers,
-                        qs_config.number_of_incorrect_answers
-                    ));
-                }
-            }
-        }
-    }
-
-    for qs in question_sets {
-        for question in &qs.questions {
-            if question.text.trim().is_empty() {
-                return Err(format!("Question {:?} has empty text", question.id));
-            }
-            let has_correct_answer = question.answers.iter().any(|a| a.is_correct);
-            if !has_correct_answer {
-                return Err(format!("Question {:?} has no correct answers", question.id));
-            }
-            for answer in &question.answers {
-                if answer.text.trim().is_empty() {
-                    return Err(format!(
-                        "Answer {:?} in question {:?} has empty text",
-                        answer.id, question.id
-                    ));
-                }
-            }
-        }
-    }
-
-    Ok(())
+    issues
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -274,42 +937,118 @@ pub struct ExamInput {
     pub config: prisma::ExamEnvironmentConfig,
 }
 
-#[derive(Debug, Clone)]
-struct QuestionSetConfigWithQuestions {
-    config: prisma::ExamEnvironmentQuestionSetConfig,
-    question_sets: Vec<prisma::ExamEnvironmentQuestionSet>,
+impl From<prisma::ExamEnvironmentExam> for ExamInput {
+    fn from(exam: prisma::ExamEnvironmentExam) -> Self {
+        Self {
+            id: exam.id,
+            question_sets: exam.question_sets,
+            config: exam.config,
+        }
+    }
 }
 
-const TIMEOUT_IN_MS: u64 = 5_000;
+/// Exam with only the fields needed for generation. Other fields are defaulted.
+impl From<ExamInput> for prisma::ExamEnvironmentExam {
+    fn from(exam: ExamInput) -> Self {
+        Self {
+            id: exam.id,
+            question_sets: exam.question_sets,
+            config: exam.config,
+            ..Default::default()
+        }
+    }
+}
+
+/// Generation with the seed it was generated from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeededGeneratedExam {
+    pub seed: u64,
+    #[serde(rename = "generatedExam")]
+    pub generated_exam: prisma::ExamEnvironmentGeneratedExam,
+}
 
 /// Generates an exam for the user, based on the exam configuration.
-pub fn generate_exam(exam: ExamInput) -> Result<prisma::ExamEnvironmentGeneratedExam, Error> {
-    let start_time = Instant::now();
-    let timeout = Duration::from_millis(TIMEOUT_IN_MS);
+///
+/// Question sets and questions are picked randomly, and a generation is always found if the config allows one.
+/// The random seed is traced, so the generation can be reproduced with `generate_exam_with_seed`.
+/// Difficulty is not balanced; use `difficulty::generate_exam_with_difficulty` to keep the expected score within a band.
+pub fn generate_exam(
+    exam: impl Into<ExamInput>,
+) -> Result<prisma::ExamEnvironmentGeneratedExam, Error> {
+    let exam = exam.into();
+    let seed = rand::rng().random();
+    trace!(seed, exam_id = %exam.id, "generating exam");
+    generate_exam_with_seed(exam, seed).map(|seeded| seeded.generated_exam)
+}
 
-    let mut rng = rand::rng();
+/// Generates an exam from a seed.
+///
+/// The same exam and seed always generate the same question sets, questions and answers, in the same order.
+/// Only the generation `id` differs.
+pub fn generate_exam_with_seed(
+    exam: impl Into<ExamInput>,
+    seed: u64,
+) -> Result<SeededGeneratedExam, Error> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let generated_exam = generate_exam_with_rng(exam, &mut rng)?;
+    Ok(SeededGeneratedExam {
+        seed,
+        generated_exam,
+    })
+}
 
-    // Shuffle question sets and their questions/answers
-    let mut shuffled_question_sets: Vec<prisma::ExamEnvironmentQuestionSet> = exam
-        .question_sets
-        .into_iter()
-        .map(|mut qs| {
-            let mut shuffled_questions: Vec<prisma::ExamEnvironmentMultipleChoiceQuestion> = qs
-                .questions
-                .into_iter()
-                .filter(|q| !q.deprecated)
-                .map(|mut q| {
-                    q.answers.shuffle(&mut rng);
-                    q
-                })
-                .collect();
-            shuffled_questions.shuffle(&mut rng);
-            qs.questions = shuffled_questions;
-            qs
-        })
-        .collect();
-    shuffled_question_sets.shuffle(&mut rng);
+/// Generates an exam, using `rng` for all random choices.
+pub fn generate_exam_with_rng<R: Rng>(
+    exam: impl Into<ExamInput>,
+    rng: &mut R,
+) -> Result<prisma::ExamEnvironmentGeneratedExam, Error> {
+    generate_exam_with_exposure(&exam.into(), rng, None)
+}
+
+/// Generates an exam, preferring the least exposed question sets, questions and answers, if `exposure` is given.
+pub(crate) fn generate_exam_with_exposure<R: Rng>(
+    exam: &ExamInput,
+    rng: &mut R,
+    exposure: Option<&ExposureCounts>,
+) -> Result<prisma::ExamEnvironmentGeneratedExam, Error> {
+    generate_exam_with_config_indices(exam, rng, exposure).map(|(generation, _)| generation)
+}
+
+/// Generates an exam as `generate_exam_with_exposure`, along with the index into `config.question_sets`
+/// each generated question set was allocated to.
+pub(crate) fn generate_exam_with_config_indices<R: Rng>(
+    exam: &ExamInput,
+    rng: &mut R,
+    exposure: Option<&ExposureCounts>,
+) -> Result<(prisma::ExamEnvironmentGeneratedExam, Vec<usize>), Error> {
+    let allocations = match solve_with_rng(&exam.question_sets, &exam.config, Some(rng), exposure) {
+        Feasibility::Feasible(allocations) => allocations,
+        Feasibility::Infeasible(constraints) => {
+            return Err(get_infeasible_error(exam, &constraints));
+        }
+    };
+    generate_exam_from_allocations(exam, allocations, rng, exposure)
+}
+
+/// Error generating an exam whose config constraints conflict
+pub(crate) fn get_infeasible_error(exam: &ExamInput, constraints: &[ConfigConstraint]) -> Error {
+    let constraints: Vec<String> = constraints.iter().map(|c| c.to_string()).collect();
+    Error::Generation(format!(
+        "Invalid Exam Configuration for exam \"{}\". Conflicting constraints: {}.",
+        exam.id,
+        constraints.join("; ")
+    ))
+}
 
+/// Generates an exam from the question sets and questions allocated by the solver.
+///
+/// Also returns the index into `config.question_sets` each generated question set was allocated to.
+pub(crate) fn generate_exam_from_allocations<R: Rng>(
+    exam: &ExamInput,
+    mut allocations: Vec<QuestionSetAllocation>,
+    rng: &mut R,
+    exposure: Option<&ExposureCounts>,
+) -> Result<(prisma::ExamEnvironmentGeneratedExam, Vec<usize>), Error> {
     if exam.config.question_sets.is_empty() {
         return Err(Error::Generation(format!(
             "{}: Invalid exam config - no question sets config.",
@@ -317,430 +1056,80 @@ pub fn generate_exam(exam: ExamInput) -> Result<prisma::ExamEnvironmentGenerated
         )));
     }
 
-    // Convert question set config by type: [[all question sets of type], [another type], ...]
-    let mut type_converted_question_sets_config: Vec<
-        Vec<prisma::ExamEnvironmentQuestionSetConfig>,
-    > = Vec::new();
-    for config in exam.config.question_sets.iter() {
-        if let Some(type_group) = type_converted_question_sets_config
-            .iter_mut()
-            .find(|group| group.first().map(|c| &c._type) == Some(&config._type))
-        {
-            type_group.push(config.clone());
-        } else {
-            type_converted_question_sets_config.push(vec![config.clone()]);
-        }
-    }
-
-    // Sort each type group randomly (heuristic for retry)
-    for group in type_converted_question_sets_config.iter_mut() {
-        group.shuffle(&mut rng);
-    }
-
-    let sorted_question_sets_config: Vec<prisma::ExamEnvironmentQuestionSetConfig> =
-        type_converted_question_sets_config
-            .into_iter()
-            .flatten()
-            .collect();
-
-    // Move all questions from set that are used to fulfill tag config.
-    let mut question_sets_config_with_questions: Vec<QuestionSetConfigWithQuestions> =
-        sorted_question_sets_config
-            .into_iter()
-            .map(|config| QuestionSetConfigWithQuestions {
-                config,
-                question_sets: Vec::new(),
-            })
-            .collect();
-
-    // Sort tag config by number of tags in descending order.
-    let mut sorted_tag_config = exam.config.tags.clone();
-    sorted_tag_config.sort_by(|a, b| b.group.len().cmp(&a.group.len()));
-
-    // Main allocation loop
-    'question_sets_config_loop: for qsc_with_qs in question_sets_config_with_questions.iter_mut() {
-        'sorted_tag_config_loop: for tag_config in sorted_tag_config.iter_mut() {
-            // Collect questions to remove (question_set_id, question_id)
-            let mut questions_to_remove: Vec<(ObjectId, ObjectId)> = Vec::new();
-
-            for question_set in shuffled_question_sets
-                .iter_mut()
-                .filter(|sqs| sqs._type == qsc_with_qs.config._type)
-            {
-                // If questionSet does not have enough questions for config, do not consider.
-                if qsc_with_qs.config.number_of_questions > question_set.questions.len() as i64 {
-                    trace!(
-                        number_of_questions = question_set.questions.len(),
-                        "skipping. not enough questions in question set"
-                    );
-                    continue;
-                }
-                // If tagConfig is finished, skip.
-                if tag_config.number_of_questions == 0 {
-                    trace!(?tag_config.group, "skipping. tag config fulfilled");
-                    continue 'sorted_tag_config_loop;
-                }
-                // If questionSetConfig has been fulfilled, skip.
-                if is_question_set_config_fulfilled(qsc_with_qs) {
-                    trace!(?qsc_with_qs, "skipping. question set config fulfilled");
-                    continue 'question_sets_config_loop;
-                }
-
-                // Store question_set id and metadata before mutable borrow
-                let question_set_id = question_set.id;
-                let question_set_type = question_set._type.clone();
-                let question_set_context = question_set.context.clone();
-
-                // Find question with at least all tags in the set.
-                let questions: Vec<&mut prisma::ExamEnvironmentMultipleChoiceQuestion> =
-                    question_set
-                        .questions
-                        .iter_mut()
-                        .filter(|q| tag_config.group.iter().all(|t| q.tags.contains(t)))
-                        .collect();
-
-                // trace!(
-                //     number_of_questions = questions.len(),
-                //     ?tag_config,
-                //     "questions fulfilling tags"
-                // );
-
-                for question in questions {
-                    // Does question fulfill criteria for questionSetConfig:
-                    let number_of_correct_answers =
-                        question.answers.iter().filter(|a| a.is_correct).count() as i64;
-                    let number_of_incorrect_answers =
-                        question.answers.iter().filter(|a| !a.is_correct).count() as i64;
-
-                    if qsc_with_qs.config.number_of_correct_answers <= number_of_correct_answers
-                        && qsc_with_qs.config.number_of_incorrect_answers
-                            <= number_of_incorrect_answers
-                    {
-                        if is_question_set_config_fulfilled(qsc_with_qs) {
-                            continue 'question_sets_config_loop;
-                        }
-
-                        // Push questionSet if it does not exist. Otherwise, just push question
-                        let qscqs = qsc_with_qs
-                            .question_sets
-                            .iter_mut()
-                            .find(|qs| qs.id == question_set_id);
-
-                        let question_with_correct_number_of_answers =
-                            get_question_with_random_answers(question, &qsc_with_qs.config)?;
-
-                        if let Some(existing_qs) = qscqs {
-                            if existing_qs.questions.len()
-                                == qsc_with_qs.config.number_of_questions as usize
-                            {
-                                break;
-                            }
-                            existing_qs
-                                .questions
-                                .push(question_with_correct_number_of_answers);
-                        } else {
-                            if qsc_with_qs.question_sets.len()
-                                == qsc_with_qs.config.number_of_set as usize
-                            {
-                                break;
-                            }
-                            // Create new question set from stored metadata
-                            let new_question_set = prisma::ExamEnvironmentQuestionSet {
-                                id: question_set_id,
-                                _type: question_set_type.clone(),
-                                context: question_set_context.clone(),
-                                questions: vec![question_with_correct_number_of_answers],
-                            };
-                            qsc_with_qs.question_sets.push(new_question_set);
-                        }
-
-                        // Mark question for removal
-                        questions_to_remove.push((question_set_id, question.id));
-
-                        tag_config.number_of_questions -= 1;
-                    }
-                }
-            }
+    trace!(
+        number_of_sets = allocations.len(),
+        "question sets allocated"
+    );
 
-            // Remove marked questions after iteration
-            for (qs_id, q_id) in questions_to_remove {
-                if let Some(qs) = shuffled_question_sets.iter_mut().find(|qs| qs.id == qs_id) {
-                    qs.questions.retain(|q| q.id != q_id);
-                }
-            }
-        }
+    // Keep question sets of the same config together
+    allocations.sort_by_key(|a| a.config_index);
+    let config_indices = allocations.iter().map(|a| a.config_index).collect();
 
-        // Add questions to questionSetsConfigWithQuestions until fulfilled.
-        while !is_question_set_config_fulfilled(qsc_with_qs) {
-            if start_time.elapsed() > timeout {
-                return Err(Error::Generation(format!(
-                    "Unable to generate exam within {}ms",
-                    TIMEOUT_IN_MS
-                )));
-            }
+    let mut question_sets = Vec::with_capacity(allocations.len());
+    for allocation in allocations {
+        let question_set = exam
+            .question_sets
+            .iter()
+            .find(|qs| qs.id == allocation.question_set_id)
+            .ok_or_else(|| {
+                Error::Generation(format!(
+                    "Question set {} not found in exam {}.",
+                    allocation.question_set_id, exam.id
+                ))
+            })?;
+        let question_set_config = &exam.config.question_sets[allocation.config_index];
 
-            // Ensure all questionSets ARE FULL
-            if (qsc_with_qs.config.number_of_set as usize) > qsc_with_qs.question_sets.len() {
-                // Find a question set with enough questions and answers to fulfill config
-                let question_set = shuffled_question_sets
+        let questions = allocation
+            .question_ids
+            .iter()
+            .map(|question_id| {
+                let question = question_set
+                    .questions
                     .iter()
-                    .find(|qs| {
-                        if qs._type == qsc_with_qs.config._type && qs.questions.len() >= qsc_with_qs.config.number_of_questions as usize
-                            {
-                                let questions: Vec<&prisma::ExamEnvironmentMultipleChoiceQuestion> = qs
-                                    .questions
-                                    .iter()
-                                    .filter(|q| {
-                                        let number_of_correct_answers =
-                                            q.answers.iter().filter(|a| a.is_correct).count()
-                                                as i64;
-                                        let number_of_incorrect_answers =
-                                            q.answers.iter().filter(|a| !a.is_correct).count()
-                                                as i64;
-                                        number_of_correct_answers
-                                            >= qsc_with_qs.config.number_of_correct_answers
-                                            && number_of_incorrect_answers
-                                                >= qsc_with_qs.config.number_of_incorrect_answers
-                                    })
-                                    .collect();
-
-                                return questions.len()
-                                    >= qsc_with_qs.config.number_of_questions as usize;
-                        }
-
-                        false
-                    })
-                    .cloned()
+                    .find(|q| q.id == *question_id)
                     .ok_or_else(|| {
                         Error::Generation(format!(
-                            "Invalid Exam Configuration for {}. Not enough questions for question type {:?}.",
-                            exam.id, qsc_with_qs.config._type
+                            "Question {} not found in question set {}.",
+                            question_id, question_set.id
                         ))
                     })?;
-
-                trace!("question set found to fulfill");
-
-                // Remove questionSet from shuffledQuestionSets
-                let question_set_id = question_set.id;
-                shuffled_question_sets.retain(|qs| qs.id != question_set_id);
-
-                // Find question with enough answers to fulfill config
-                let questions: Vec<prisma::ExamEnvironmentMultipleChoiceQuestion> = question_set
-                    .questions
-                    .iter()
-                    .filter(|q| {
-                        let number_of_correct_answers =
-                            q.answers.iter().filter(|a| a.is_correct).count() as i64;
-                        let number_of_incorrect_answers =
-                            q.answers.iter().filter(|a| !a.is_correct).count() as i64;
-                        number_of_correct_answers >= qsc_with_qs.config.number_of_correct_answers
-                            && number_of_incorrect_answers
-                                >= qsc_with_qs.config.number_of_incorrect_answers
-                    })
-                    .cloned()
-                    .collect();
-
-                let num_to_add = qsc_with_qs.config.number_of_questions as usize;
-                let questions_with_correct_answers: Result<
-                    Vec<prisma::ExamEnvironmentMultipleChoiceQuestion>,
-                    Error,
-                > = questions
-                    .iter()
-                    .take(num_to_add)
-                    .map(|q| get_question_with_random_answers(q, &qsc_with_qs.config))
-                    .collect();
-
-                let mut question_set_with_correct_number_of_answers = question_set.clone();
-                question_set_with_correct_number_of_answers.questions =
-                    questions_with_correct_answers?;
-
-                trace!(
-                    number_of_questions =
-                        question_set_with_correct_number_of_answers.questions.len(),
-                    "pushing question set to question set with config"
-                );
-                qsc_with_qs
-                    .question_sets
-                    .push(question_set_with_correct_number_of_answers);
-            }
-
-            // Ensure all existing questionSets have correct number of questions
-            for question_set in qsc_with_qs.question_sets.iter_mut() {
-                if (question_set.questions.len() as i64) < qsc_with_qs.config.number_of_questions {
-                    let questions: Vec<prisma::ExamEnvironmentMultipleChoiceQuestion> =
-                        shuffled_question_sets
-                            .iter()
-                            .find(|qs| qs.id == question_set.id)
-                            .ok_or_else(|| {
-                                Error::Generation(
-                                    format!(
-                                    "Invalid Exam Configuration for {}. Not enough questions for question type {:?}.",
-                                    exam.id, qsc_with_qs.config._type
-                                ))
-                            })?
-                            .questions
-                            .iter()
-                            .filter(|q| !question_set.questions.iter().any(|qsq| qsq.id == q.id))
-                            .cloned()
-                            .collect();
-
-                    let questions_with_enough_answers: Vec<
-                        prisma::ExamEnvironmentMultipleChoiceQuestion,
-                    > = questions
-                        .into_iter()
-                        .filter(|q| {
-                            let number_of_correct_answers =
-                                q.answers.iter().filter(|a| a.is_correct).count() as i64;
-                            let number_of_incorrect_answers =
-                                q.answers.iter().filter(|a| !a.is_correct).count() as i64;
-                            number_of_correct_answers
-                                >= qsc_with_qs.config.number_of_correct_answers
-                                && number_of_incorrect_answers
-                                    >= qsc_with_qs.config.number_of_incorrect_answers
-                        })
-                        .collect();
-
-                    // Push as many questions as needed to fulfill questionSetConfig
-                    let num_to_add = (qsc_with_qs.config.number_of_questions as usize)
-                        - question_set.questions.len();
-                    let questions_to_add: Vec<prisma::ExamEnvironmentMultipleChoiceQuestion> =
-                        questions_with_enough_answers
-                            .into_iter()
-                            .take(num_to_add)
-                            .collect();
-
-                    let questions_with_correct_answers: Result<
-                        Vec<prisma::ExamEnvironmentMultipleChoiceQuestion>,
-                        Error,
-                    > = questions_to_add
-                        .iter()
-                        .map(|q| get_question_with_random_answers(q, &qsc_with_qs.config))
-                        .collect();
-
-                    question_set
-                        .questions
-                        .extend(questions_with_correct_answers?);
-
-                    // Remove questions from shuffledQuestionSets
-                    for q in questions_to_add.iter() {
-                        if let Some(qs) = shuffled_question_sets
-                            .iter_mut()
-                            .find(|qs| qs.id == question_set.id)
-                        {
-                            qs.questions.retain(|qs_q| qs_q.id != q.id);
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    trace!("finished question set config");
-
-    // Update tag config for cases where one question fulfills multiple tag configs
-    for qsc_with_qs in question_sets_config_with_questions.iter() {
-        for question_set in qsc_with_qs.question_sets.iter() {
-            for question in question_set.questions.iter() {
-                for tag_config in sorted_tag_config.iter_mut() {
-                    if tag_config.number_of_questions <= 0 {
-                        continue;
-                    }
-                    if tag_config.group.iter().all(|t| question.tags.contains(t)) {
-                        tag_config.number_of_questions -= 1;
-                    }
-                }
-            }
-        }
-    }
-
-    trace!("finished updating tag config post question set config");
-
-    // Verify all tag configs are fulfilled
-    for tag_config in sorted_tag_config.iter() {
-        if tag_config.number_of_questions > 0 {
-            return Err(Error::Generation(format!(
-                "Invalid Exam Configuration for exam \"{}\". Not enough questions for tag group \"{}\".",
-                exam.id,
-                tag_config.group.join(",")
-            )));
-        }
-    }
-
-    trace!("all tag configs are fulfilled");
-
-    // Build the final generated exam structure
-    let question_sets: Vec<prisma::ExamEnvironmentGeneratedQuestionSet> =
-        question_sets_config_with_questions
-            .into_iter()
-            .flat_map(|qsc| {
-                qsc.question_sets.into_iter().map(|qs| {
-                    let questions: Vec<prisma::ExamEnvironmentGeneratedMultipleChoiceQuestion> = qs
-                        .questions
-                        .into_iter()
-                        .map(|q| {
-                            let answers: Vec<ObjectId> =
-                                q.answers.into_iter().map(|a| a.id).collect();
-                            prisma::ExamEnvironmentGeneratedMultipleChoiceQuestion {
-                                id: q.id,
-                                answers,
-                            }
-                        })
-                        .collect();
-                    prisma::ExamEnvironmentGeneratedQuestionSet {
-                        id: qs.id,
-                        questions,
-                    }
+                let question =
+                    get_question_with_random_answers(question, question_set_config, rng, exposure)?;
+                Ok(prisma::ExamEnvironmentGeneratedMultipleChoiceQuestion {
+                    id: question.id,
+                    answers: question.answers.into_iter().map(|a| a.id).collect(),
                 })
             })
-            .collect();
+            .collect::<Result<Vec<_>, Error>>()?;
 
-    Ok(prisma::ExamEnvironmentGeneratedExam {
+        question_sets.push(prisma::ExamEnvironmentGeneratedQuestionSet {
+            id: question_set.id,
+            questions,
+        });
+    }
+
+    let generation = prisma::ExamEnvironmentGeneratedExam {
         id: ObjectId::new(),
         exam_id: exam.id,
         question_sets,
         deprecated: false,
         version: 1,
-    })
+    };
+    Ok((generation, config_indices))
 }
 
-/// Returns the configs that are not fulfilled
-fn is_question_set_config_fulfilled(qsc_with_qs: &QuestionSetConfigWithQuestions) -> bool {
-    let enough_of_set =
-        qsc_with_qs.config.number_of_set as usize == qsc_with_qs.question_sets.len();
-
-    if !enough_of_set {
-        trace!(
-            expected = qsc_with_qs.config.number_of_set,
-            actual = qsc_with_qs.question_sets.len(),
-            "not enough of set"
-        );
-    }
-
-    let enough_questions = qsc_with_qs.question_sets.iter().all(|qs| {
-        let num_questions = qs.questions.len();
-        let expected_num_questions = qsc_with_qs.config.number_of_questions as usize;
-        if num_questions != expected_num_questions {
-            trace!(
-                expected = expected_num_questions,
-                actual = num_questions,
-                "number of questions required in config does not match number of questions"
-            );
-        }
-
-        num_questions == expected_num_questions
-    });
-
-    enough_of_set && enough_questions
-}
-
-/// Gets random answers for a question.
-fn get_question_with_random_answers(
+/// Gets random answers for a question, preferring the least exposed answers if `exposure` is given.
+pub(crate) fn get_question_with_random_answers(
     question: &prisma::ExamEnvironmentMultipleChoiceQuestion,
     question_set_config: &prisma::ExamEnvironmentQuestionSetConfig,
+    rng: &mut impl Rng,
+    exposure: Option<&ExposureCounts>,
 ) -> Result<prisma::ExamEnvironmentMultipleChoiceQuestion, Error> {
-    let mut rng = rand::rng();
     let mut random_answers = question.answers.clone();
-    random_answers.shuffle(&mut rng);
+    random_answers.shuffle(rng);
+    if let Some(exposure) = exposure {
+        random_answers.sort_by_key(|a| exposure.get(&a.id).copied().unwrap_or(0));
+    }
 
     let incorrect_answers: Vec<prisma::ExamEnvironmentAnswer> = random_answers
         .iter()
@@ -770,3 +1159,435 @@ fn get_question_with_random_answers(
     result.answers = answers;
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use prisma::{
+        ExamEnvironmentAnswer, ExamEnvironmentConfig, ExamEnvironmentExam,
+        ExamEnvironmentExamAttempt, ExamEnvironmentGeneratedExam,
+        ExamEnvironmentGeneratedMultipleChoiceQuestion, ExamEnvironmentGeneratedQuestionSet,
+        ExamEnvironmentMultipleChoiceQuestion, ExamEnvironmentMultipleChoiceQuestionAttempt,
+        ExamEnvironmentQuestionSet, ExamEnvironmentQuestionSetAttempt,
+        ExamEnvironmentQuestionSetConfig, ExamEnvironmentTagConfig,
+    };
+
+    use super::{
+        ConfigIssueCode, ConfigIssueSeverity, CreditPolicy, ExamInput, QuestionSetWeight,
+        ScoringPolicy, TagWeight, calculate_score, calculate_score_with_policy, generate_exam,
+        generate_exam_with_seed, get_answer_credit, get_tag_breakdown, validate_config,
+    };
+
+    /// Exam with one question set of `n` questions, each with a correct first answer and an incorrect second answer
+    fn exam_with_generation(n: usize) -> (ExamEnvironmentExam, ExamEnvironmentGeneratedExam) {
+        let questions: Vec<ExamEnvironmentMultipleChoiceQuestion> = (0..n)
+            .map(|_| ExamEnvironmentMultipleChoiceQuestion {
+                id: ObjectId::new(),
+                answers: vec![
+                    ExamEnvironmentAnswer {
+                        id: ObjectId::new(),
+                        is_correct: true,
+                        text: "correct".to_string(),
+                    },
+                    ExamEnvironmentAnswer {
+                        id: ObjectId::new(),
+                        is_correct: false,
+                        text: "incorrect".to_string(),
+                    },
+                ],
+                ..Default::default()
+            })
+            .collect();
+        let question_set = ExamEnvironmentQuestionSet {
+            id: ObjectId::new(),
+            questions,
+            ..Default::default()
+        };
+        let generated_exam = ExamEnvironmentGeneratedExam {
+            id: ObjectId::new(),
+            exam_id: ObjectId::new(),
+            question_sets: vec![ExamEnvironmentGeneratedQuestionSet {
+                id: question_set.id,
+                questions: question_set
+                    .questions
+                    .iter()
+                    .map(|q| ExamEnvironmentGeneratedMultipleChoiceQuestion {
+                        id: q.id,
+                        answers: q.answers.iter().map(|a| a.id).collect(),
+                    })
+                    .collect(),
+            }],
+            deprecated: false,
+            version: 1,
+        };
+        let exam = ExamEnvironmentExam {
+            id: generated_exam.exam_id,
+            question_sets: vec![question_set],
+            config: ExamEnvironmentConfig {
+                passing_percent: 50.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        (exam, generated_exam)
+    }
+
+    /// Attempt answering the first questions with the answer at the given index
+    fn attempt(exam: &ExamEnvironmentExam, selected: &[usize]) -> ExamEnvironmentExamAttempt {
+        let question_set = &exam.question_sets[0];
+        ExamEnvironmentExamAttempt {
+            question_sets: vec![ExamEnvironmentQuestionSetAttempt {
+                id: question_set.id,
+                questions: question_set
+                    .questions
+                    .iter()
+                    .zip(selected)
+                    .map(|(q, i)| ExamEnvironmentMultipleChoiceQuestionAttempt {
+                        id: q.id,
+                        answers: vec![q.answers[*i].id],
+                        ..Default::default()
+                    })
+                    .collect(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn score_report() {
+        let (exam, generated_exam) = exam_with_generation(4);
+
+        let score_report =
+            calculate_score(&exam, &generated_exam, &attempt(&exam, &[0, 0, 1])).unwrap();
+        assert_eq!(score_report.correct, 2);
+        assert_eq!(score_report.total, 4);
+        assert_eq!(score_report.percentage, 50.0);
+        assert!(score_report.passed);
+        let questions: Vec<(bool, bool)> = score_report.question_sets[0]
+            .questions
+            .iter()
+            .map(|q| (q.answered, q.is_correct))
+            .collect();
+        assert_eq!(
+            questions,
+            vec![(true, true), (true, true), (true, false), (false, false)]
+        );
+
+        let score_report =
+            calculate_score(&exam, &generated_exam, &attempt(&exam, &[1, 0])).unwrap();
+        assert_eq!(score_report.percentage, 25.0);
+        assert!(!score_report.passed);
+    }
+
+    #[test]
+    fn score_errors() {
+        let (exam, generated_exam) = exam_with_generation(2);
+
+        // Attempted question not in generation
+        let mut bad_attempt = attempt(&exam, &[0]);
+        bad_attempt.question_sets[0].questions[0].id = ObjectId::new();
+        assert!(calculate_score(&exam, &generated_exam, &bad_attempt).is_err());
+
+        // Generation without questions
+        let (exam, mut generated_exam) = exam_with_generation(0);
+        generated_exam.question_sets.clear();
+        assert!(calculate_score(&exam, &generated_exam, &attempt(&exam, &[])).is_err());
+    }
+
+    #[test]
+    fn tag_breakdown() {
+        let (mut exam, generated_exam) = exam_with_generation(3);
+        exam.question_sets[0].questions[0].tags = vec!["a".to_string(), "b".to_string()];
+        exam.question_sets[0].questions[1].tags = vec!["a".to_string()];
+        exam.question_sets[0].questions[2].tags = vec!["b".to_string()];
+        exam.config.tags = vec![
+            ExamEnvironmentTagConfig {
+                group: vec!["a".to_string(), "b".to_string()],
+                ..Default::default()
+            },
+            ExamEnvironmentTagConfig {
+                group: vec!["b".to_string()],
+                ..Default::default()
+            },
+        ];
+
+        let tag_breakdown =
+            get_tag_breakdown(&exam, &generated_exam, &attempt(&exam, &[0, 1])).unwrap();
+
+        let tags: Vec<(&str, usize, usize, usize, f64)> = tag_breakdown
+            .tags
+            .iter()
+            .map(|t| {
+                (
+                    t.tag.as_str(),
+                    t.result.total,
+                    t.result.answered,
+                    t.result.correct,
+                    t.result.percentage,
+                )
+            })
+            .collect();
+        assert_eq!(tags, vec![("a", 2, 2, 1, 50.0), ("b", 2, 1, 1, 50.0)]);
+
+        let groups: Vec<(usize, usize, usize)> = tag_breakdown
+            .groups
+            .iter()
+            .map(|g| (g.result.total, g.result.answered, g.result.correct))
+            .collect();
+        assert_eq!(groups, vec![(1, 1, 1), (2, 1, 1)]);
+    }
+
+    #[test]
+    fn answer_credit() {
+        let answers: Vec<ExamEnvironmentAnswer> = [true, true, true, false, false]
+            .iter()
+            .map(|is_correct| ExamEnvironmentAnswer {
+                id: ObjectId::new(),
+                is_correct: *is_correct,
+                text: "answer".to_string(),
+            })
+            .collect();
+        let generated: Vec<ObjectId> = answers.iter().map(|a| a.id).collect();
+        // Two of three correct answers, and one incorrect answer
+        let selected = vec![generated[0], generated[1], generated[3]];
+
+        let credit = |policy| get_answer_credit(&answers, &generated, &selected, &policy);
+        assert_eq!(credit(CreditPolicy::AllOrNothing), 0.0);
+        assert!((credit(CreditPolicy::CorrectMinusIncorrect) - 1.0 / 3.0).abs() < 1e-9);
+        assert!((credit(CreditPolicy::Proportional) - 3.0 / 5.0).abs() < 1e-9);
+
+        let all = generated.clone();
+        assert_eq!(
+            get_answer_credit(
+                &answers,
+                &generated,
+                &all,
+                &CreditPolicy::CorrectMinusIncorrect
+            ),
+            1.0 / 3.0
+        );
+        let correct = generated[..3].to_vec();
+        for policy in [
+            CreditPolicy::AllOrNothing,
+            CreditPolicy::CorrectMinusIncorrect,
+            CreditPolicy::Proportional,
+        ] {
+            assert_eq!(
+                get_answer_credit(&answers, &generated, &correct, &policy),
+                1.0
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_score() {
+        let (mut exam, generated_exam) = exam_with_generation(4);
+        exam.question_sets[0].questions[0].tags = vec!["important".to_string()];
+        let attempt = attempt(&exam, &[0, 1, 1, 1]);
+
+        let policy = ScoringPolicy {
+            tag_weights: vec![TagWeight {
+                tag: "important".to_string(),
+                weight: 3.0,
+            }],
+            ..Default::default()
+        };
+        let score_report =
+            calculate_score_with_policy(&exam, &generated_exam, &attempt, &policy).unwrap();
+        assert_eq!(score_report.correct, 1);
+        assert_eq!(score_report.score, 3.0);
+        assert_eq!(score_report.max_score, 6.0);
+        assert_eq!(score_report.percentage, 50.0);
+        assert!(score_report.passed);
+
+        let policy = ScoringPolicy {
+            question_set_weights: vec![QuestionSetWeight {
+                id: exam.question_sets[0].id,
+                weight: -1.0,
+            }],
+            ..Default::default()
+        };
+        assert!(calculate_score_with_policy(&exam, &generated_exam, &attempt, &policy).is_err());
+    }
+
+    #[test]
+    fn config_issues() {
+        let (mut exam, _) = exam_with_generation(4);
+        for question in exam.question_sets[0].questions.iter_mut() {
+            question.text = "question".to_string();
+            question.tags = vec!["a".to_string()];
+        }
+        exam.config.name = "exam".to_string();
+        exam.config.question_sets = vec![ExamEnvironmentQuestionSetConfig {
+            number_of_set: 1,
+            number_of_questions: 3,
+            number_of_correct_answers: 1,
+            number_of_incorrect_answers: 1,
+            ..Default::default()
+        }];
+        assert_eq!(validate_config(&exam), vec![]);
+
+        exam.config.name = String::new();
+        exam.config.tags = vec![
+            ExamEnvironmentTagConfig {
+                group: vec!["b".to_string()],
+                number_of_questions: 1,
+            },
+            ExamEnvironmentTagConfig {
+                group: vec!["a".to_string()],
+                number_of_questions: 1,
+            },
+        ];
+        let questions = &mut exam.question_sets[0].questions;
+        questions[0].deprecated = true;
+        questions[1].deprecated = true;
+        questions[2].tags = vec![];
+        questions[2].answers[1].text = "correct".to_string();
+        questions[3].answers[0].text = String::new();
+        let duplicate_answer_id = questions[2].answers[1].id;
+        let empty_answer_id = questions[3].answers[0].id;
+
+        let issues: Vec<(ConfigIssueSeverity, ConfigIssueCode, Option<ObjectId>)> =
+            validate_config(&exam)
+                .into_iter()
+                .map(|issue| (issue.severity, issue.code, issue.answer_id))
+                .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (ConfigIssueSeverity::Error, ConfigIssueCode::EmptyName, None),
+                (
+                    ConfigIssueSeverity::Error,
+                    ConfigIssueCode::DeprecatedQuestionsExhaustPool,
+                    None
+                ),
+                (
+                    ConfigIssueSeverity::Error,
+                    ConfigIssueCode::DeprecatedQuestionsExhaustPool,
+                    None
+                ),
+                (
+                    ConfigIssueSeverity::Error,
+                    ConfigIssueCode::NotEnoughTagQuestions,
+                    None
+                ),
+                (
+                    ConfigIssueSeverity::Error,
+                    ConfigIssueCode::EmptyAnswerText,
+                    Some(empty_answer_id)
+                ),
+                (
+                    ConfigIssueSeverity::Warning,
+                    ConfigIssueCode::DeprecatedQuestionsShrinkPool,
+                    None
+                ),
+                (
+                    ConfigIssueSeverity::Warning,
+                    ConfigIssueCode::QuestionWithoutTags,
+                    None
+                ),
+                (
+                    ConfigIssueSeverity::Warning,
+                    ConfigIssueCode::DuplicateAnswerText,
+                    Some(duplicate_answer_id)
+                ),
+            ]
+        );
+    }
+
+    /// Exam input with a question set for each entry of `question_set_tags`, and a question for each tag in the entry
+    fn exam_input(question_set_tags: &[&[&str]], tags: &[(&str, i64)]) -> ExamInput {
+        let question_sets = question_set_tags
+            .iter()
+            .map(|question_tags| ExamEnvironmentQuestionSet {
+                id: ObjectId::new(),
+                questions: question_tags
+                    .iter()
+                    .map(|tag| ExamEnvironmentMultipleChoiceQuestion {
+                        id: ObjectId::new(),
+                        tags: vec![tag.to_string()],
+                        answers: [true, false]
+                            .iter()
+                            .map(|is_correct| ExamEnvironmentAnswer {
+                                id: ObjectId::new(),
+                                is_correct: *is_correct,
+                                ..Default::default()
+                            })
+                            .collect(),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        ExamInput {
+            id: ObjectId::new(),
+            question_sets,
+            config: ExamEnvironmentConfig {
+                question_sets: vec![ExamEnvironmentQuestionSetConfig {
+                    number_of_set: 2,
+                    number_of_questions: 2,
+                    number_of_correct_answers: 1,
+                    number_of_incorrect_answers: 1,
+                    ..Default::default()
+                }],
+                tags: tags
+                    .iter()
+                    .map(|(tag, number_of_questions)| ExamEnvironmentTagConfig {
+                        group: vec![tag.to_string()],
+                        number_of_questions: *number_of_questions,
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn generate_exam_finds_only_generation() {
+        // Only the two "a" questions of the first set, and both questions of the second set, fulfill the tags
+        let exam = exam_input(&[&["b", "a", "a"], &["b", "b"]], &[("a", 2), ("b", 2)]);
+        let a_questions: Vec<ObjectId> = exam.question_sets[0].questions[1..]
+            .iter()
+            .map(|q| q.id)
+            .collect();
+
+        for _ in 0..20 {
+            let generation = generate_exam(exam.clone()).unwrap();
+            assert_eq!(generation.exam_id, exam.id);
+            assert_eq!(generation.question_sets.len(), 2);
+            let first = generation
+                .question_sets
+                .iter()
+                .find(|qs| qs.id == exam.question_sets[0].id)
+                .unwrap();
+            let mut question_ids: Vec<ObjectId> = first.questions.iter().map(|q| q.id).collect();
+            question_ids.sort();
+            let mut expected = a_questions.clone();
+            expected.sort();
+            assert_eq!(question_ids, expected);
+            assert!(first.questions.iter().all(|q| q.answers.len() == 2));
+        }
+
+        let exam = exam_input(&[&["b", "a", "a"], &["b", "b"]], &[("a", 2), ("b", 3)]);
+        let err = generate_exam(exam).unwrap_err();
+        assert!(err.to_string().contains("Conflicting constraints"));
+    }
+
+    #[test]
+    fn seeded_generation() {
+        let exam = exam_input(
+            &[&["a", "a", "a"], &["a", "a", "a"], &["a", "a"]],
+            &[("a", 3)],
+        );
+
+        let first = generate_exam_with_seed(exam.clone(), 42).unwrap();
+        let second = generate_exam_with_seed(exam, 42).unwrap();
+        assert_eq!(first.seed, 42);
+        assert_eq!(
+            first.generated_exam.question_sets,
+            second.generated_exam.question_sets
+        );
+    }
+}
@@ -0,0 +1,953 @@
+//! Exact feasibility checking for exam configs
+//!
+//! A generation assigns distinct question sets to each `config.question_sets` entry, and picks
+//! `number_of_questions` non-deprecated questions with enough correct/incorrect answers from each,
+//! such that every `config.tags` group is covered by at least `number_of_questions` picked questions.
+//!
+//! Questions are only distinguished by which tag groups they satisfy (their signature), and question sets
+//! offering the same choices are interchangeable. The search is a depth-first search over question sets,
+//! grouped by the choices they offer, with memoized failures and pruning on the remaining capacity.
+//! A memoized failure also rules out the same state needing more tag config questions.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use mongodb::bson::oid::ObjectId;
+use rand::{RngCore, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{exposure::ExposureCounts, misc::ExamInput};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Feasibility {
+    /// A generation exists. Contains one such allocation of questions.
+    Feasible(Vec<QuestionSetAllocation>),
+    /// No generation exists. Contains a small set of constraints which cannot be satisfied together.
+    Infeasible(Vec<ConfigConstraint>),
+}
+
+impl Feasibility {
+    pub fn is_feasible(&self) -> bool {
+        matches!(self, Feasibility::Feasible(_))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuestionSetAllocation {
+    #[serde(rename = "questionSetId")]
+    pub question_set_id: ObjectId,
+    /// Index into `config.question_sets`
+    #[serde(rename = "configIndex")]
+    pub config_index: usize,
+    #[serde(rename = "questionIds")]
+    pub question_ids: Vec<ObjectId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ConfigConstraint {
+    QuestionSet {
+        /// Index into `config.question_sets`
+        index: usize,
+        config: prisma::ExamEnvironmentQuestionSetConfig,
+    },
+    Tag {
+        /// Index into `config.tags`
+        index: usize,
+        config: prisma::ExamEnvironmentTagConfig,
+    },
+}
+
+impl fmt::Display for ConfigConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigConstraint::QuestionSet { index, config } => write!(
+                f,
+                "question set config {index}: {} {:?} set(s) of {} question(s) with {} correct and {} incorrect answer(s)",
+                config.number_of_set,
+                config._type,
+                config.number_of_questions,
+                config.number_of_correct_answers,
+                config.number_of_incorrect_answers
+            ),
+            ConfigConstraint::Tag { index, config } => write!(
+                f,
+                "tag config {index}: {} question(s) tagged \"{}\"",
+                config.number_of_questions,
+                config.group.join(",")
+            ),
+        }
+    }
+}
+
+/// Checks whether any generation satisfies the exam config.
+///
+/// If not, the returned constraints conflict, and are irreducible (removing any one of them makes the rest satisfiable)
+/// unless the exam is too large to prove so within the explanation's search budget.
+pub fn check_feasibility(exam: &ExamInput) -> Feasibility {
+    get_feasibility(&exam.question_sets, &exam.config)
+}
+
+pub(crate) fn get_feasibility(
+    question_sets: &[prisma::ExamEnvironmentQuestionSet],
+    config: &prisma::ExamEnvironmentConfig,
+) -> Feasibility {
+    solve_with_rng(question_sets, config, None, None)
+}
+
+/// Checks whether any generation satisfies the config, without explaining why not.
+pub(crate) fn is_feasible(
+    question_sets: &[prisma::ExamEnvironmentQuestionSet],
+    config: &prisma::ExamEnvironmentConfig,
+) -> bool {
+    let problem = Problem::new(question_sets, config, SearchOrder::MostUseful, None, None);
+    matches!(
+        problem.solve(
+            &problem.initial_slots(),
+            &problem.tag_requirements,
+            usize::MAX
+        ),
+        Outcome::Found(_)
+    )
+}
+
+/// Finds a generation, picking question sets and questions randomly if an rng is given.
+/// If `exposure` is given, the least exposed question sets and questions are preferred.
+///
+/// A generation is always found if one exists: only the random order search is cut short,
+/// and the final search is exhaustive.
+pub(crate) fn solve_with_rng(
+    question_sets: &[prisma::ExamEnvironmentQuestionSet],
+    config: &prisma::ExamEnvironmentConfig,
+    mut rng: Option<&mut dyn RngCore>,
+    exposure: Option<&ExposureCounts>,
+) -> Feasibility {
+    // A fully random search order gives the most varied generations, but can be slow for tight configs.
+    // So, it is only tried for a while before falling back to searching the most useful sets first.
+    if let Some(rng) = rng.as_mut() {
+        let problem = Problem::new(
+            question_sets,
+            config,
+            SearchOrder::Random,
+            Some(&mut **rng),
+            exposure,
+        );
+        match problem.solve(
+            &problem.initial_slots(),
+            &problem.tag_requirements,
+            RANDOM_ORDER_NODE_BUDGET,
+        ) {
+            Outcome::Found(allocation) => {
+                return Feasibility::Feasible(problem.allocate(&allocation, Some(&mut **rng)));
+            }
+            Outcome::Infeasible => return Feasibility::Infeasible(problem.explain(config)),
+            Outcome::Unknown => {}
+        }
+    }
+
+    let problem = Problem::new(
+        question_sets,
+        config,
+        SearchOrder::MostUseful,
+        rng.as_mut().map(|r| &mut **r as &mut dyn RngCore),
+        exposure,
+    );
+    match problem.solve(
+        &problem.initial_slots(),
+        &problem.tag_requirements,
+        usize::MAX,
+    ) {
+        Outcome::Found(allocation) => Feasibility::Feasible(problem.allocate(&allocation, rng)),
+        Outcome::Infeasible => Feasibility::Infeasible(problem.explain(config)),
+        Outcome::Unknown => unreachable!("the search is unbounded"),
+    }
+}
+
+struct Problem<'a> {
+    question_sets: &'a [prisma::ExamEnvironmentQuestionSet],
+    exposure: Option<&'a ExposureCounts>,
+    /// `number_of_set` for each question set config
+    number_of_sets: Vec<usize>,
+    /// `number_of_questions` for each tag config
+    tag_requirements: Vec<usize>,
+    /// Question sets usable by at least one config, grouped by the choices they offer
+    groups: Vec<Group>,
+    /// Question sets, in search order, as (group index, index in group)
+    positions: Vec<(usize, usize)>,
+    /// `eligible_sets[c][p]`: number of sets from position `p` onwards usable by question set config `c`
+    eligible_sets: Vec<Vec<usize>>,
+}
+
+/// Question sets offering the same choices
+struct Group {
+    /// Choices as (question set config index, questions satisfying each tag config, capped at the requirement)
+    choices: Vec<(usize, Vec<usize>)>,
+    /// For each question set config, the most questions satisfying each tag config, and in total, of any choice
+    bounds: Vec<Option<Bound>>,
+    sets: Vec<GroupSet>,
+}
+
+impl Group {
+    fn new(
+        choices: Vec<(usize, Vec<usize>)>,
+        sets: Vec<GroupSet>,
+        number_of_configs: usize,
+    ) -> Self {
+        let bounds = (0..number_of_configs)
+            .map(|c| {
+                choices
+                    .iter()
+                    .filter(|(config_index, _)| *config_index == c)
+                    .fold(None, |bound: Option<Bound>, (_, coverage)| {
+                        let total = coverage.iter().sum();
+                        Some(match bound {
+                            Some((most, most_total)) => (
+                                most.iter().zip(coverage).map(|(a, b)| *a.max(b)).collect(),
+                                most_total.max(total),
+                            ),
+                            None => (coverage.clone(), total),
+                        })
+                    })
+            })
+            .collect();
+        Self {
+            choices,
+            bounds,
+            sets,
+        }
+    }
+
+    /// Most questions satisfying each tag config, and in total, of any choice for configs with free slots
+    fn bound(&self, slots: &[Option<usize>]) -> Option<Bound> {
+        self.bounds
+            .iter()
+            .zip(slots)
+            .filter(|(_, s)| **s != Some(0))
+            .filter_map(|(bound, _)| bound.as_ref())
+            .fold(None, |acc: Option<Bound>, (most, total)| {
+                Some(match acc {
+                    Some((acc_most, acc_total)) => (
+                        acc_most.iter().zip(most).map(|(a, b)| *a.max(b)).collect(),
+                        acc_total.max(*total),
+                    ),
+                    None => (most.clone(), *total),
+                })
+            })
+    }
+}
+
+struct GroupSet {
+    /// Index into `question_sets`
+    index: usize,
+    /// For each choice, the number of questions to pick from each signature class
+    picks: Vec<Picks>,
+}
+
+/// Most failed states remembered during a search
+const MAX_FAILED_STATES: usize = 1_000_000;
+/// Most states searched when checking whether a constraint can be dropped from an explanation
+const EXPLAIN_NODE_BUDGET: usize = 20_000;
+/// Most states searched in random order, before searching the most useful sets first
+const RANDOM_ORDER_NODE_BUDGET: usize = 50_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SearchOrder {
+    /// Sets and choices covering the most tag config questions first
+    MostUseful,
+    Random,
+}
+
+enum Outcome {
+    Found(Allocation),
+    Infeasible,
+    /// The search gave up
+    Unknown,
+}
+
+struct SearchState {
+    failed: FailedStates,
+    num_failed: usize,
+    /// Remaining states to search
+    nodes_left: usize,
+}
+
+impl SearchState {
+    /// Whether the state is known to fail.
+    ///
+    /// A state fails if a searched state at the same position and slots failed with no fewer choices
+    /// (a lower or equal min choice), and no more requirements.
+    fn has_failed(
+        &self,
+        position: usize,
+        min_choice: usize,
+        slots: &[Option<usize>],
+        requirements: &[usize],
+    ) -> bool {
+        self.failed
+            .get(&(position, slots.to_vec()))
+            .is_some_and(|failed| {
+                failed.iter().any(|(m, r)| {
+                    *m <= min_choice && r.iter().zip(requirements).all(|(a, b)| a <= b)
+                })
+            })
+    }
+
+    fn insert_failed(
+        &mut self,
+        position: usize,
+        min_choice: usize,
+        slots: &[Option<usize>],
+        requirements: &[usize],
+    ) {
+        // Failed states only speed up the search, so are dropped instead of growing without bound
+        if self.num_failed >= MAX_FAILED_STATES {
+            self.failed.clear();
+            self.num_failed = 0;
+        }
+        let failed = self.failed.entry((position, slots.to_vec())).or_default();
+        let before = failed.len();
+        // Drop the states this one implies
+        failed.retain(|(m, r)| {
+            !(min_choice <= *m && requirements.iter().zip(r).all(|(a, b)| a <= b))
+        });
+        failed.push((min_choice, requirements.to_vec()));
+        self.num_failed = self.num_failed + failed.len() - before;
+    }
+}
+
+/// Chosen choice index for each position, `None` if the set is unused
+type Allocation = Vec<Option<usize>>;
+/// Most questions satisfying each tag config, and in total
+type Bound = (Vec<usize>, usize);
+/// Signature classes (question indices) and the number of questions to pick from each
+type Picks = Vec<(Vec<usize>, usize)>;
+/// Searched states without an allocation, as (position, slots) -> (min choice, requirements)
+type FailedStates = HashMap<(usize, Vec<Option<usize>>), Vec<(usize, Vec<usize>)>>;
+
+impl<'a> Problem<'a> {
+    fn new(
+        question_sets: &'a [prisma::ExamEnvironmentQuestionSet],
+        config: &prisma::ExamEnvironmentConfig,
+        order: SearchOrder,
+        mut rng: Option<&mut dyn RngCore>,
+        exposure: Option<&'a ExposureCounts>,
+    ) -> Self {
+        let number_of_sets: Vec<usize> = config
+            .question_sets
+            .iter()
+            .map(|c| c.number_of_set.max(0) as usize)
+            .collect();
+        let tag_requirements: Vec<usize> = config
+            .tags
+            .iter()
+            .map(|t| t.number_of_questions.max(0) as usize)
+            .collect();
+
+        let mut groups: BTreeMap<Vec<(usize, Vec<usize>)>, Vec<GroupSet>> = BTreeMap::new();
+        for (index, question_set) in question_sets.iter().enumerate() {
+            let mut choices = vec![];
+            let mut picks = vec![];
+            for (config_index, qs_config) in config.question_sets.iter().enumerate() {
+                if qs_config._type != question_set._type {
+                    continue;
+                }
+                for (coverage, pick) in get_choices(question_set, qs_config, &config.tags) {
+                    choices.push((config_index, coverage));
+                    picks.push(pick);
+                }
+            }
+            if choices.is_empty() {
+                continue;
+            }
+            groups
+                .entry(choices)
+                .or_default()
+                .push(GroupSet { index, picks });
+        }
+
+        let mut groups: Vec<Group> = groups
+            .into_iter()
+            .map(|(choices, sets)| Group::new(choices, sets, number_of_sets.len()))
+            .collect();
+
+        // Shuffle, if an rng is given, so ties are broken randomly
+        if let Some(rng) = rng.as_mut() {
+            groups.shuffle(rng);
+            for group in groups.iter_mut() {
+                group.sets.shuffle(rng);
+            }
+        }
+        let exposure_of = |set: &GroupSet| {
+            exposure
+                .and_then(|e| e.get(&question_sets[set.index].id))
+                .copied()
+                .unwrap_or(0)
+        };
+        if exposure.is_some() {
+            // Interchangeable sets are searched least exposed first, at no cost to the search
+            for group in groups.iter_mut() {
+                group.sets.sort_by_key(exposure_of);
+            }
+        }
+        match order {
+            SearchOrder::MostUseful => groups.sort_by_key(|g| {
+                std::cmp::Reverse(g.bounds.iter().flatten().map(|(_, t)| *t).max())
+            }),
+            SearchOrder::Random if exposure.is_some() => {
+                groups.sort_by_key(|g| g.sets.iter().map(exposure_of).min())
+            }
+            SearchOrder::Random => {}
+        }
+        for group in groups.iter_mut() {
+            let mut choice_order: Vec<usize> = (0..group.choices.len()).collect();
+            if let Some(rng) = rng.as_mut() {
+                choice_order.shuffle(rng);
+            }
+            if order == SearchOrder::MostUseful {
+                choice_order
+                    .sort_by_key(|i| std::cmp::Reverse(group.choices[*i].1.iter().sum::<usize>()));
+            }
+            // Keep each set's picks aligned with the choices
+            group.choices = choice_order
+                .iter()
+                .map(|i| group.choices[*i].clone())
+                .collect();
+            for set in group.sets.iter_mut() {
+                set.picks = choice_order.iter().map(|i| set.picks[*i].clone()).collect();
+            }
+        }
+
+        let positions: Vec<(usize, usize)> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(g, group)| (0..group.sets.len()).map(move |s| (g, s)))
+            .collect();
+
+        let mut eligible_sets = vec![vec![0; positions.len() + 1]; number_of_sets.len()];
+        for (p, (g, _)) in positions.iter().enumerate().rev() {
+            for (c, eligible) in eligible_sets.iter_mut().enumerate() {
+                let usable = groups[*g].bounds[c].is_some();
+                eligible[p] = eligible[p + 1] + usize::from(usable);
+            }
+        }
+
+        Self {
+            question_sets,
+            exposure,
+            number_of_sets,
+            tag_requirements,
+            groups,
+            positions,
+            eligible_sets,
+        }
+    }
+
+    fn initial_slots(&self) -> Vec<Option<usize>> {
+        self.number_of_sets.iter().map(|n| Some(*n)).collect()
+    }
+
+    /// Finds an allocation filling `slots` sets for each question set config,
+    /// and covering `requirements` questions for each tag config.
+    /// Question set configs with `None` slots may take any number of sets.
+    ///
+    /// Gives up after searching `node_budget` states.
+    fn solve(
+        &self,
+        slots: &[Option<usize>],
+        requirements: &[usize],
+        node_budget: usize,
+    ) -> Outcome {
+        let mut state = SearchState {
+            failed: HashMap::new(),
+            num_failed: 0,
+            nodes_left: node_budget,
+        };
+        let mut allocation = vec![None; self.positions.len()];
+        let mut slots = slots.to_vec();
+        let mut requirements = requirements.to_vec();
+        if self.search(
+            0,
+            0,
+            &mut slots,
+            &mut requirements,
+            &mut allocation,
+            &mut state,
+        ) {
+            Outcome::Found(allocation)
+        } else if state.nodes_left == 0 {
+            Outcome::Unknown
+        } else {
+            Outcome::Infeasible
+        }
+    }
+
+    /// `min_choice` breaks symmetry between interchangeable sets:
+    /// sets in the same group take non-decreasing choices, with unused sets last.
+    fn search(
+        &self,
+        position: usize,
+        min_choice: usize,
+        slots: &mut Vec<Option<usize>>,
+        requirements: &mut Vec<usize>,
+        allocation: &mut Allocation,
+        state: &mut SearchState,
+    ) -> bool {
+        if slots.iter().all(|s| s.is_none_or(|s| s == 0)) && requirements.iter().all(|r| *r == 0) {
+            for a in allocation.iter_mut().skip(position) {
+                *a = None;
+            }
+            return true;
+        }
+        if state.nodes_left == 0 {
+            return false;
+        }
+        state.nodes_left -= 1;
+        if position == self.positions.len() {
+            return false;
+        }
+        if slots
+            .iter()
+            .enumerate()
+            .any(|(c, s)| s.is_some_and(|s| s > self.eligible_sets[c][position]))
+            || !self.can_cover(position, slots, requirements)
+        {
+            return false;
+        }
+
+        if state.has_failed(position, min_choice, slots, requirements) {
+            return false;
+        }
+
+        let (g, _) = self.positions[position];
+        let group = &self.groups[g];
+        let next_in_group = self
+            .positions
+            .get(position + 1)
+            .is_some_and(|(next_g, _)| *next_g == g);
+
+        for (choice, (config_index, coverage)) in group.choices.iter().enumerate().skip(min_choice)
+        {
+            if slots[*config_index] == Some(0) {
+                continue;
+            }
+
+            let previous_requirements = requirements.clone();
+            if let Some(s) = slots[*config_index].as_mut() {
+                *s -= 1;
+            }
+            for (r, cov) in requirements.iter_mut().zip(coverage) {
+                *r = r.saturating_sub(*cov);
+            }
+            allocation[position] = Some(choice);
+
+            let next_min_choice = if next_in_group { choice } else { 0 };
+            if self.search(
+                position + 1,
+                next_min_choice,
+                slots,
+                requirements,
+                allocation,
+                state,
+            ) {
+                return true;
+            }
+
+            if let Some(s) = slots[*config_index].as_mut() {
+                *s += 1;
+            }
+            *requirements = previous_requirements;
+        }
+
+        // Leave this set unused
+        allocation[position] = None;
+        let next_min_choice = if next_in_group {
+            group.choices.len()
+        } else {
+            0
+        };
+        if self.search(
+            position + 1,
+            next_min_choice,
+            slots,
+            requirements,
+            allocation,
+            state,
+        ) {
+            return true;
+        }
+
+        state.insert_failed(position, min_choice, slots, requirements);
+        false
+    }
+
+    /// Optimistic check whether the sets from `position` onwards can cover the tag requirements.
+    ///
+    /// Each set is assumed to take its best choice, and only as many sets as there are free slots are counted,
+    /// both across all question set configs, and for each config separately.
+    fn can_cover(&self, position: usize, slots: &[Option<usize>], requirements: &[usize]) -> bool {
+        if requirements.iter().all(|r| *r == 0) {
+            return true;
+        }
+        let remaining_sets = self.positions.len() - position;
+        let free_slots = slots
+            .iter()
+            .try_fold(0, |acc, s| s.map(|s| acc + s))
+            .unwrap_or(remaining_sets)
+            .min(remaining_sets);
+
+        // Remaining groups, as (group, remaining sets in group)
+        let (first, index_in_group) = self.positions[position];
+        let remaining_groups: Vec<(&Group, usize)> = self.groups[first..]
+            .iter()
+            .enumerate()
+            .map(|(i, group)| {
+                let skipped = if i == 0 { index_in_group } else { 0 };
+                (group, group.sets.len() - skipped)
+            })
+            .collect();
+        let combined: Vec<(Option<Bound>, usize)> = remaining_groups
+            .iter()
+            .map(|(group, count)| (group.bound(slots), *count))
+            .collect();
+
+        let top_sum = |mut values: Vec<(usize, usize)>, limit: usize| -> usize {
+            values.sort_by_key(|(v, _)| std::cmp::Reverse(*v));
+            let mut left = limit;
+            let mut sum = 0;
+            for (value, count) in values {
+                let n = count.min(left);
+                sum += value * n;
+                left -= n;
+                if left == 0 {
+                    break;
+                }
+            }
+            sum
+        };
+        // Most of `value` coverable by the remaining sets
+        let capacity = |value: &dyn Fn(&Bound) -> usize| -> usize {
+            let across_configs = top_sum(
+                combined
+                    .iter()
+                    .filter_map(|(bound, count)| bound.as_ref().map(|b| (value(b), *count)))
+                    .collect(),
+                free_slots,
+            );
+            let per_config: usize = slots
+                .iter()
+                .enumerate()
+                .map(|(c, s)| {
+                    let values = remaining_groups
+                        .iter()
+                        .filter_map(|(group, count)| {
+                            group.bounds[c].as_ref().map(|b| (value(b), *count))
+                        })
+                        .collect();
+                    top_sum(values, s.unwrap_or(remaining_sets))
+                })
+                .sum();
+            across_configs.min(per_config)
+        };
+
+        let total_requirement: usize = requirements.iter().sum();
+        capacity(&|(_, total)| *total) >= total_requirement
+            && requirements
+                .iter()
+                .enumerate()
+                .all(|(t, r)| *r == 0 || capacity(&|(most, _)| most[t]) >= *r)
+    }
+
+    /// Deletion filter: drops each constraint whose removal keeps the rest unsatisfiable.
+    ///
+    /// Removing a question set config allows it any number of sets.
+    /// A constraint is kept if the rest cannot be proven unsatisfiable within `EXPLAIN_NODE_BUDGET`,
+    /// so the explanation always conflicts, but may not be minimal for large exams.
+    fn explain(&self, config: &prisma::ExamEnvironmentConfig) -> Vec<ConfigConstraint> {
+        let mut slots = self.initial_slots();
+        let mut requirements = self.tag_requirements.clone();
+
+        for c in 0..slots.len() {
+            let previous = slots[c];
+            slots[c] = None;
+            let outcome = self.solve(&slots, &requirements, EXPLAIN_NODE_BUDGET);
+            if !matches!(outcome, Outcome::Infeasible) {
+                slots[c] = previous;
+            }
+        }
+        for t in 0..requirements.len() {
+            let previous = requirements[t];
+            requirements[t] = 0;
+            let outcome = self.solve(&slots, &requirements, EXPLAIN_NODE_BUDGET);
+            if !matches!(outcome, Outcome::Infeasible) {
+                requirements[t] = previous;
+            }
+        }
+
+        let question_set_constraints =
+            slots
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.is_some())
+                .map(|(index, _)| ConfigConstraint::QuestionSet {
+                    index,
+                    config: config.question_sets[index].clone(),
+                });
+        let tag_constraints = requirements
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| **r > 0)
+            .map(|(index, _)| ConfigConstraint::Tag {
+                index,
+                config: config.tags[index].clone(),
+            });
+        question_set_constraints.chain(tag_constraints).collect()
+    }
+
+    /// Picks the questions for each allocated set
+    fn allocate(
+        &self,
+        allocation: &Allocation,
+        mut rng: Option<&mut dyn RngCore>,
+    ) -> Vec<QuestionSetAllocation> {
+        let mut allocations = vec![];
+        for (position, choice) in allocation.iter().enumerate() {
+            let Some(choice) = choice else {
+                continue;
+            };
+            let (g, s) = self.positions[position];
+            let group = &self.groups[g];
+            let set = &group.sets[s];
+            let question_set = &self.question_sets[set.index];
+
+            let mut question_ids = vec![];
+            for (class, count) in &set.picks[*choice] {
+                let mut class = class.clone();
+                if let Some(rng) = rng.as_mut() {
+                    class.shuffle(rng);
+                }
+                if let Some(exposure) = self.exposure {
+                    class.sort_by_key(|q| {
+                        exposure
+                            .get(&question_set.questions[*q].id)
+                            .copied()
+                            .unwrap_or(0)
+                    });
+                }
+                question_ids.extend(
+                    class
+                        .iter()
+                        .take(*count)
+                        .map(|q| question_set.questions[*q].id),
+                );
+            }
+            if let Some(rng) = rng.as_mut() {
+                question_ids.shuffle(rng);
+            }
+
+            allocations.push(QuestionSetAllocation {
+                question_set_id: question_set.id,
+                config_index: group.choices[*choice].0,
+                question_ids,
+            });
+        }
+        allocations
+    }
+}
+
+/// Distinct ways of picking questions from a question set for a question set config,
+/// as (questions satisfying each tag config, capped at the requirement; questions to pick from each signature class).
+fn get_choices(
+    question_set: &prisma::ExamEnvironmentQuestionSet,
+    qs_config: &prisma::ExamEnvironmentQuestionSetConfig,
+    tags: &[prisma::ExamEnvironmentTagConfig],
+) -> Vec<(Vec<usize>, Picks)> {
+    let number_of_questions = qs_config.number_of_questions.max(0) as usize;
+    // Generated questions need at least one correct and one incorrect answer
+    let number_of_correct_answers = qs_config.number_of_correct_answers.max(1) as usize;
+    let number_of_incorrect_answers = qs_config.number_of_incorrect_answers.max(1) as usize;
+
+    // Group eligible questions by the tag configs they satisfy
+    let mut classes: BTreeMap<Vec<bool>, Vec<usize>> = BTreeMap::new();
+    for (i, question) in question_set.questions.iter().enumerate() {
+        if question.deprecated {
+            continue;
+        }
+        let correct = question.answers.iter().filter(|a| a.is_correct).count();
+        let incorrect = question.answers.len() - correct;
+        if correct < number_of_correct_answers || incorrect < number_of_incorrect_answers {
+            continue;
+        }
+        let signature: Vec<bool> = tags
+            .iter()
+            .map(|t| t.group.iter().all(|tag| question.tags.contains(tag)))
+            .collect();
+        classes.entry(signature).or_default().push(i);
+    }
+
+    let eligible: usize = classes.values().map(|c| c.len()).sum();
+    if eligible < number_of_questions {
+        return vec![];
+    }
+
+    // (questions picked, capped coverage) -> picks per class
+    let mut states: BTreeMap<(usize, Vec<usize>), Vec<usize>> = BTreeMap::new();
+    states.insert((0, vec![0; tags.len()]), vec![]);
+    for (signature, questions) in classes.iter() {
+        let mut next_states = BTreeMap::new();
+        for ((picked, coverage), picks) in states {
+            let most = questions.len().min(number_of_questions - picked);
+            for n in 0..=most {
+                let next_coverage: Vec<usize> = coverage
+                    .iter()
+                    .zip(signature)
+                    .zip(tags)
+                    .map(|((cov, satisfies), tag)| {
+                        let added = if *satisfies { n } else { 0 };
+                        (cov + added).min(tag.number_of_questions.max(0) as usize)
+                    })
+                    .collect();
+                let mut next_picks = picks.clone();
+                next_picks.push(n);
+                next_states
+                    .entry((picked + n, next_coverage))
+                    .or_insert(next_picks);
+            }
+        }
+        states = next_states;
+    }
+
+    let classes: Vec<&Vec<usize>> = classes.values().collect();
+    states
+        .into_iter()
+        .filter(|((picked, _), _)| *picked == number_of_questions)
+        .map(|((_, coverage), picks)| {
+            let picks = classes
+                .iter()
+                .zip(picks)
+                .filter(|(_, n)| *n > 0)
+                .map(|(class, n)| ((*class).clone(), n))
+                .collect();
+            (coverage, picks)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use prisma::{
+        ExamEnvironmentAnswer, ExamEnvironmentConfig, ExamEnvironmentMultipleChoiceQuestion,
+        ExamEnvironmentQuestionSet, ExamEnvironmentQuestionSetConfig, ExamEnvironmentTagConfig,
+    };
+
+    use crate::misc::ExamInput;
+
+    use super::{ConfigConstraint, Feasibility, check_feasibility};
+
+    fn question(tags: &[&str]) -> ExamEnvironmentMultipleChoiceQuestion {
+        ExamEnvironmentMultipleChoiceQuestion {
+            id: ObjectId::new(),
+            text: "question".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            answers: [true, false]
+                .iter()
+                .map(|is_correct| ExamEnvironmentAnswer {
+                    id: ObjectId::new(),
+                    is_correct: *is_correct,
+                    text: "answer".to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn question_set(
+        questions: Vec<ExamEnvironmentMultipleChoiceQuestion>,
+    ) -> ExamEnvironmentQuestionSet {
+        ExamEnvironmentQuestionSet {
+            id: ObjectId::new(),
+            questions,
+            ..Default::default()
+        }
+    }
+
+    fn tag_config(tag: &str, number_of_questions: i64) -> ExamEnvironmentTagConfig {
+        ExamEnvironmentTagConfig {
+            group: vec![tag.to_string()],
+            number_of_questions,
+        }
+    }
+
+    /// Exam needing `number_of_set` sets of 2 questions, from a set tagged [a, a, b] and a set tagged [b, b]
+    fn exam(number_of_set: i64, tags: Vec<ExamEnvironmentTagConfig>) -> ExamInput {
+        ExamInput {
+            id: ObjectId::new(),
+            question_sets: vec![
+                question_set(vec![question(&["a"]), question(&["a"]), question(&["b"])]),
+                question_set(vec![question(&["b"]), question(&["b"])]),
+            ],
+            config: ExamEnvironmentConfig {
+                question_sets: vec![ExamEnvironmentQuestionSetConfig {
+                    number_of_set,
+                    number_of_questions: 2,
+                    number_of_correct_answers: 1,
+                    number_of_incorrect_answers: 1,
+                    ..Default::default()
+                }],
+                tags,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn feasible_allocation() {
+        let exam = exam(2, vec![tag_config("a", 2), tag_config("b", 2)]);
+
+        let Feasibility::Feasible(allocations) = check_feasibility(&exam) else {
+            panic!("config should be feasible");
+        };
+        assert_eq!(allocations.len(), 2);
+        let a_questions: Vec<ObjectId> = exam.question_sets[0].questions[..2]
+            .iter()
+            .map(|q| q.id)
+            .collect();
+        let first = allocations
+            .iter()
+            .find(|a| a.question_set_id == exam.question_sets[0].id)
+            .unwrap();
+        assert_eq!(first.question_ids, a_questions);
+    }
+
+    #[test]
+    fn infeasible_explanation() {
+        // Each constraint is satisfiable on its own, but 2 questions cannot cover 2 "a" and 1 "b"
+        let exam = exam(1, vec![tag_config("a", 2), tag_config("b", 1)]);
+
+        let Feasibility::Infeasible(constraints) = check_feasibility(&exam) else {
+            panic!("config should be infeasible");
+        };
+        let constraints: Vec<(bool, usize)> = constraints
+            .iter()
+            .map(|c| match c {
+                ConfigConstraint::QuestionSet { index, .. } => (true, *index),
+                ConfigConstraint::Tag { index, .. } => (false, *index),
+            })
+            .collect();
+        assert_eq!(constraints, vec![(true, 0), (false, 0), (false, 1)]);
+
+        // An unrelated tag config is not part of the explanation
+        let exam = exam_with_unrelated_tag();
+        let Feasibility::Infeasible(constraints) = check_feasibility(&exam) else {
+            panic!("config should be infeasible");
+        };
+        assert_eq!(constraints.len(), 3);
+    }
+
+    fn exam_with_unrelated_tag() -> ExamInput {
+        let mut exam = exam(1, vec![tag_config("a", 2), tag_config("b", 1)]);
+        exam.question_sets[0].questions[0]
+            .tags
+            .push("c".to_string());
+        exam.config.tags.push(tag_config("c", 1));
+        exam
+    }
+}
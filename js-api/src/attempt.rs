@@ -20,3 +20,52 @@ pub fn get_moderation_score(attempt: JsValue, events: JsValue) -> JsValue {
 
     to_value(&res).unwrap()
 }
+
+#[wasm_bindgen]
+pub fn get_attempt_stats(attempt: JsValue) -> JsValue {
+    let attempt: exam_utils::attempt::Attempt = from_value(attempt).unwrap();
+
+    let res = exam_utils::attempt::get_attempt_stats(attempt);
+
+    to_value(&res).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn get_submission_gap_stats(attempt: JsValue) -> JsValue {
+    let attempt: prisma::ExamEnvironmentExamAttempt = from_value(attempt).unwrap();
+
+    let gaps = exam_utils::attempt::get_time_between_submissions(attempt);
+    let res = exam_utils::attempt::get_submission_gap_stats(&gaps);
+
+    to_value(&res).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn get_moderation_report(attempt: JsValue, events: JsValue) -> JsValue {
+    let attempt: exam_utils::attempt::Attempt = from_value(attempt).unwrap();
+    let events: Vec<prisma::supabase::Event> = from_value(events).unwrap();
+
+    let res = exam_utils::attempt::get_moderation_report(&attempt, &events);
+
+    to_value(&res.map_err(|e| e.to_string())).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn add_question_visits(attempt: JsValue, events: JsValue) -> JsValue {
+    let mut attempt: exam_utils::attempt::Attempt = from_value(attempt).unwrap();
+    let events: Vec<prisma::supabase::Event> = from_value(events).unwrap();
+
+    exam_utils::attempt::add_question_visits(&mut attempt, &events);
+
+    to_value(&attempt).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn get_audio_question_checks(attempt: JsValue, events: JsValue) -> JsValue {
+    let attempt: exam_utils::attempt::Attempt = from_value(attempt).unwrap();
+    let events: Vec<prisma::supabase::Event> = from_value(events).unwrap();
+
+    let res = exam_utils::attempt::get_audio_question_checks(&attempt, &events);
+
+    to_value(&res).unwrap()
+}
@@ -12,3 +12,22 @@ pub fn validate_generation(val: JsValue) -> JsValue {
 
     JsValue::null()
 }
+
+#[wasm_bindgen]
+pub fn try_generate(exam: JsValue, difficulty: JsValue) -> JsValue {
+    let exam: prisma::ExamEnvironmentExam = from_value(exam).unwrap();
+    let difficulty: Option<exam_utils::difficulty::DifficultyOptions> =
+        from_value(difficulty).unwrap();
+    let res = exam_utils::generation::try_generate(exam, difficulty.as_ref());
+
+    to_value(&res.map_err(|e| e.to_string())).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn estimate_generation(exam: JsValue, options: JsValue) -> JsValue {
+    let exam: prisma::ExamEnvironmentExam = from_value(exam).unwrap();
+    let options: exam_utils::exposure::EstimateOptions = from_value(options).unwrap();
+    let estimate = exam_utils::exposure::estimate_generation(&exam.into(), &options);
+
+    to_value(&estimate).unwrap()
+}
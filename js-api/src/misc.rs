@@ -17,7 +17,7 @@ pub fn calculate_score(exam: JsValue, generation: JsValue, attempt: JsValue) ->
 
     let res = exam_utils::misc::calculate_score(&exam, &generation, &attempt);
 
-    to_value(res).unwrap()
+    to_value(&res.map_err(|e| e.to_string())).unwrap()
 }
 
 #[wasm_bindgen]
@@ -4,7 +4,10 @@ use tracing::{error, warn};
 
 #[derive(Clone, Debug)]
 pub struct EnvVars {
+    pub collusion_window_in_s: Duration,
     pub environment: Environment,
+    /// Most recent finished attempts per exam used by the item analysis
+    pub item_analysis_max_attempts: i64,
     pub moderation_length_in_s: Duration,
     pub moderation_threshold: f64,
     pub mongodb_uri: String,
@@ -93,6 +96,33 @@ impl EnvVars {
                 duration
             }
         };
+        let collusion_window_in_s = match var("COLLUSION_WINDOW_IN_S") {
+            Ok(v) => {
+                let seconds = match v.parse() {
+                    Ok(m) => m,
+                    Err(e) => {
+                        panic!(
+                            "COLLUSION_WINDOW_IN_S is not a valid whole number of seconds: {:?}",
+                            e
+                        );
+                    }
+                };
+                Duration::from_secs(seconds)
+            }
+            Err(_e) => {
+                let two_days_in_s = 2 * 24 * 60 * 60;
+                Duration::from_secs(two_days_in_s)
+            }
+        };
+        let item_analysis_max_attempts = match var("ITEM_ANALYSIS_MAX_ATTEMPTS") {
+            Ok(v) => match v.parse() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    panic!("ITEM_ANALYSIS_MAX_ATTEMPTS should be a positive whole number: {v}");
+                }
+            },
+            Err(_e) => 10_000,
+        };
         let moderation_threshold = match var("MODERATION_THRESHOLD") {
             Ok(v) => {
                 let num = match v.parse() {
@@ -106,7 +136,7 @@ impl EnvVars {
                 };
                 num
             }
-            Err(_e) => 0.25,
+            Err(_e) => 0.125,
         };
 
         let environment = match var("ENVIRONMENT") {
@@ -145,7 +175,9 @@ impl EnvVars {
         };
 
         let env_vars = Self {
+            collusion_window_in_s,
             environment,
+            item_analysis_max_attempts,
             moderation_length_in_s,
             moderation_threshold,
             mongodb_uri,
@@ -9,7 +9,10 @@ use mongodb::{
 use serde::{Deserialize, Serialize};
 
 use exam_utils::{
-    attempt::{construct_attempt, get_moderation_score},
+    analytics::{ExamItemAnalysis, ExamReliability, analyse_items, get_exam_reliability},
+    attempt::{ModerationScorer, construct_attempt, verify_attempt},
+    baseline::{ExamTimingBaseline, SpeedAnomalySignal, build_timing_baseline},
+    collusion::{CollusionConfig, detect_collusion as detect_collusion_clusters},
     misc::check_attempt_pass,
 };
 use prisma::{
@@ -23,6 +26,12 @@ use supabase_rs::SupabaseClient;
 use crate::config::EnvVars;
 
 const PRACTICE_EXAM_ID: &str = "674819431ed2e8ac8d170f5e";
+/// Most recent attempts per exam used to build timing baselines
+const TIMING_BASELINE_MAX_ATTEMPTS: i64 = 1000;
+/// Weight of the `SpeedAnomalySignal`, added to the default moderation signals when a timing baseline exists
+const SPEED_ANOMALY_WEIGHT: f64 = 0.15;
+/// Start of the feedback of moderation records flagged with integrity issues, which are never auto approved
+const INTEGRITY_ISSUES_FEEDBACK: &str = "Integrity issues";
 
 /// Auto approves old, unmoderated moderation records
 /// Creates moderation records for attempts not already in the queue
@@ -41,6 +50,8 @@ pub async fn update_moderation_collection(env_vars: &EnvVars) -> anyhow::Result<
     let generation_collection =
         get_collection::<ExamEnvironmentGeneratedExam>(&client, "ExamEnvironmentGeneratedExam")
             .await;
+    let baseline_collection =
+        get_collection::<ExamTimingBaseline>(&client, "ExamEnvironmentTimingBaseline").await;
 
     let supabase_url = &env_vars.supabase_url;
     let supabase_key = &env_vars.supabase_key;
@@ -65,6 +76,7 @@ pub async fn update_moderation_collection(env_vars: &EnvVars) -> anyhow::Result<
 
     let mut exams = HashMap::new();
     let mut generated_exams = HashMap::new();
+    let mut baselines = HashMap::new();
     let practice_exam_id =
         ObjectId::parse_str(PRACTICE_EXAM_ID).expect("static str is valid object id");
 
@@ -72,6 +84,7 @@ pub async fn update_moderation_collection(env_vars: &EnvVars) -> anyhow::Result<
     let mut num_attempts_passed = 0;
     let mut num_attempts_below_moderation_threshold = 0;
     let mut num_attempts_above_moderation_threshold = 0;
+    let mut num_attempts_with_integrity_issues = 0;
 
     while let Some(attempt) = attempts_cursor.next().await {
         let attempt = attempt.context("unable to deserialize attempt to collection")?;
@@ -156,22 +169,51 @@ pub async fn update_moderation_collection(env_vars: &EnvVars) -> anyhow::Result<
                 num_attempts_passed += 1;
                 let events = get_events_for_attempt(&supabase, &attempt.id).await?;
 
+                let baseline = if let Some(baseline) = baselines.get(&attempt.exam_id) {
+                    baseline
+                } else {
+                    let baseline = baseline_collection
+                        .find_one(doc! {"examId": &attempt.exam_id})
+                        .await
+                        .context("unable to find timing baseline for exam")?;
+                    baselines.insert(attempt.exam_id, baseline);
+                    baselines.get(&attempt.exam_id).unwrap()
+                };
+                let mut scorer = ModerationScorer::default();
+                if let Some(baseline) = baseline {
+                    scorer = scorer.with_signal(
+                        SpeedAnomalySignal::new(baseline.clone()),
+                        SPEED_ANOMALY_WEIGHT,
+                    );
+                }
+
+                let integrity_issues = verify_attempt(exam, generated_exam, &attempt);
+                if !integrity_issues.is_empty() {
+                    num_attempts_with_integrity_issues += 1;
+                    tracing::warn!(attempt = %attempt.id, num_issues = integrity_issues.len(), "attempt has integrity issues");
+                }
+
                 let attempt = construct_attempt(&exam, &generated_exam, &attempt);
-                match get_moderation_score(&attempt, &events) {
-                    Ok(moderation_score) => {
+                match scorer.report(&attempt, &events) {
+                    Ok(moderation_report) => {
+                        let moderation_score = moderation_report.score;
                         tracing::debug!(moderation_score, attempt = %attempt.id);
 
                         if moderation_score < env_vars.moderation_threshold {
                             num_attempts_below_moderation_threshold += 1;
-                            exam_moderation.status = ExamEnvironmentExamModerationStatus::Approved;
-                            exam_moderation.moderation_date = Some(now);
-                            exam_moderation.feedback = Some(format!(
-                                "Auto Approved - Moderation score: {moderation_score}"
-                            ));
+                            // Attempts with integrity issues are always left for a moderator
+                            if integrity_issues.is_empty() {
+                                exam_moderation.status =
+                                    ExamEnvironmentExamModerationStatus::Approved;
+                                exam_moderation.moderation_date = Some(now);
+                                exam_moderation.feedback =
+                                    Some(format!("Auto Approved - {moderation_report}"));
+                            } else {
+                                exam_moderation.feedback = Some(moderation_report.to_string());
+                            }
                         } else {
                             num_attempts_above_moderation_threshold += 1;
-                            exam_moderation.feedback =
-                                Some(format!("Moderation score: {moderation_score}"));
+                            exam_moderation.feedback = Some(moderation_report.to_string());
                         }
                     }
                     Err(e) => {
@@ -180,6 +222,16 @@ pub async fn update_moderation_collection(env_vars: &EnvVars) -> anyhow::Result<
                             Some(format!("Moderation score calculation error."));
                     }
                 };
+
+                if !integrity_issues.is_empty() {
+                    let issues: Vec<String> =
+                        integrity_issues.iter().map(|i| i.to_string()).collect();
+                    exam_moderation.feedback = Some(format!(
+                        "{INTEGRITY_ISSUES_FEEDBACK}: {}. {}",
+                        issues.join("; "),
+                        exam_moderation.feedback.unwrap_or_default()
+                    ));
+                }
             }
 
             // Create a moderation entry
@@ -206,7 +258,8 @@ pub async fn update_moderation_collection(env_vars: &EnvVars) -> anyhow::Result<
         num_attempts_expired,
         num_attempts_passed,
         num_attempts_below_moderation_threshold,
-        num_attempts_above_moderation_threshold
+        num_attempts_above_moderation_threshold,
+        num_attempts_with_integrity_issues
     );
 
     Ok(())
@@ -239,6 +292,8 @@ async fn get_events_for_attempt(
 }
 
 /// Auto approves old, unmoderated moderation records
+///
+/// Records flagged with integrity issues are left for a moderator.
 #[tracing::instrument(skip_all, err(Debug))]
 pub async fn auto_approve_moderation_records(env_vars: &EnvVars) -> anyhow::Result<()> {
     let client = client(&env_vars.mongodb_uri).await?;
@@ -254,11 +309,12 @@ pub async fn auto_approve_moderation_records(env_vars: &EnvVars) -> anyhow::Resu
         #[serde(rename = "submissionDate")]
         submission_date: DateTime,
     }
-    // Find pending moderation records
+    // Find pending moderation records, except those flagged with integrity issues
     let moderation_records: Vec<ExamEnvironmentExamModerationProjection> = moderation_collection
         .clone_with_type::<ExamEnvironmentExamModerationProjection>()
         .find(doc! {
-            "status": ExamEnvironmentExamModerationStatus::Pending
+            "status": ExamEnvironmentExamModerationStatus::Pending,
+            "feedback": { "$not": { "$regex": format!("^{INTEGRITY_ISSUES_FEEDBACK}") } }
         })
         .projection(doc! { "_id": true, "submissionDate": true})
         .await
@@ -297,6 +353,301 @@ pub async fn auto_approve_moderation_records(env_vars: &EnvVars) -> anyhow::Resu
     Ok(())
 }
 
+/// Rebuilds per-question timing baselines for each exam from its most recent attempts,
+/// and upserts them into the `ExamEnvironmentTimingBaseline` collection for `update_moderation_collection` to use.
+#[tracing::instrument(skip_all, err(Debug))]
+pub async fn update_timing_baselines(env_vars: &EnvVars) -> anyhow::Result<()> {
+    let client = client(&env_vars.mongodb_uri).await?;
+
+    let attempt_collection =
+        get_collection::<ExamEnvironmentExamAttempt>(&client, "ExamEnvironmentExamAttempt").await;
+    let exam_collection =
+        get_collection::<ExamEnvironmentExam>(&client, "ExamEnvironmentExam").await;
+    let generated_exam_collection =
+        get_collection::<ExamEnvironmentGeneratedExam>(&client, "ExamEnvironmentGeneratedExam")
+            .await;
+    let baseline_collection =
+        get_collection::<ExamTimingBaseline>(&client, "ExamEnvironmentTimingBaseline").await;
+
+    let practice_exam_id =
+        ObjectId::parse_str(PRACTICE_EXAM_ID).expect("static str is valid object id");
+    let exams: Vec<ExamEnvironmentExam> = exam_collection
+        .find(doc! {"_id": {"$ne": practice_exam_id}})
+        .await
+        .context("unable to find exams")?
+        .try_collect()
+        .await
+        .context("unable to deserialize exams")?;
+
+    for exam in exams.iter() {
+        let attempts: Vec<ExamEnvironmentExamAttempt> = attempt_collection
+            .find(doc! {"examId": exam.id})
+            .sort(doc! {"startTime": -1})
+            .limit(TIMING_BASELINE_MAX_ATTEMPTS)
+            .await
+            .context("unable to find attempts for exam")?
+            .try_collect()
+            .await
+            .context("unable to deserialize attempts")?;
+        if attempts.is_empty() {
+            continue;
+        }
+
+        let unique_generated_exam_ids = attempts
+            .iter()
+            .map(|a| a.generated_exam_id)
+            .collect::<std::collections::HashSet<_>>();
+        let generated_exams = generated_exam_collection
+            .find(doc! {"_id": {"$in": unique_generated_exam_ids}})
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let attempts: Vec<_> = attempts
+            .iter()
+            .filter_map(|attempt| {
+                let generated_exam = generated_exams
+                    .iter()
+                    .find(|ge| ge.id == attempt.generated_exam_id)?;
+                Some(construct_attempt(exam, generated_exam, attempt))
+            })
+            .collect();
+
+        let baseline = build_timing_baseline(exam.id, &attempts);
+        tracing::debug!(
+            exam = %exam.id,
+            num_attempts = baseline.num_attempts,
+            num_questions = baseline.questions.len(),
+            "timing baseline"
+        );
+        baseline_collection
+            .replace_one(doc! {"examId": exam.id}, &baseline)
+            .upsert(true)
+            .await
+            .context("unable to upsert timing baseline")?;
+    }
+
+    tracing::info!(num_exams = exams.len(), "updated timing baselines");
+
+    Ok(())
+}
+
+/// Analyses the questions and answers of each exam over its most recent `item_analysis_max_attempts` finished attempts,
+/// and upserts the results into the `ExamEnvironmentItemAnalysis` collection for the content team to review.
+/// The reliability of each exam and its generations is upserted into the `ExamEnvironmentReliability` collection.
+#[tracing::instrument(skip_all, err(Debug))]
+pub async fn update_item_analyses(env_vars: &EnvVars) -> anyhow::Result<()> {
+    let client = client(&env_vars.mongodb_uri).await?;
+
+    let attempt_collection =
+        get_collection::<ExamEnvironmentExamAttempt>(&client, "ExamEnvironmentExamAttempt").await;
+    let exam_collection =
+        get_collection::<ExamEnvironmentExam>(&client, "ExamEnvironmentExam").await;
+    let generated_exam_collection =
+        get_collection::<ExamEnvironmentGeneratedExam>(&client, "ExamEnvironmentGeneratedExam")
+            .await;
+    let analysis_collection =
+        get_collection::<ExamItemAnalysis>(&client, "ExamEnvironmentItemAnalysis").await;
+    let reliability_collection =
+        get_collection::<ExamReliability>(&client, "ExamEnvironmentReliability").await;
+
+    let now = DateTime::now();
+    let practice_exam_id =
+        ObjectId::parse_str(PRACTICE_EXAM_ID).expect("static str is valid object id");
+    let exams: Vec<ExamEnvironmentExam> = exam_collection
+        .find(doc! {"_id": {"$ne": practice_exam_id}})
+        .await
+        .context("unable to find exams")?
+        .try_collect()
+        .await
+        .context("unable to deserialize exams")?;
+
+    for exam in exams.iter() {
+        // Attempts still in progress would count their unanswered questions as incorrect
+        let latest_start_time =
+            DateTime::from_millis(now.timestamp_millis() - exam.config.total_time_in_s * 1000);
+        let attempts: Vec<ExamEnvironmentExamAttempt> = attempt_collection
+            .find(doc! {"examId": exam.id, "startTime": {"$lt": latest_start_time}})
+            .sort(doc! {"startTime": -1})
+            .limit(env_vars.item_analysis_max_attempts)
+            .await
+            .context("unable to find attempts for exam")?
+            .try_collect()
+            .await
+            .context("unable to deserialize attempts")?;
+        if attempts.is_empty() {
+            continue;
+        }
+
+        let unique_generated_exam_ids = attempts
+            .iter()
+            .map(|a| a.generated_exam_id)
+            .collect::<std::collections::HashSet<_>>();
+        let generated_exams = generated_exam_collection
+            .find(doc! {"_id": {"$in": unique_generated_exam_ids}})
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let reliability = get_exam_reliability(exam, &generated_exams, &attempts);
+        tracing::debug!(
+            exam = %exam.id,
+            num_attempts = reliability.reliability.num_attempts,
+            kr20 = ?reliability.reliability.kr20,
+            "exam reliability"
+        );
+        reliability_collection
+            .replace_one(doc! {"examId": exam.id}, &reliability)
+            .upsert(true)
+            .await
+            .context("unable to upsert exam reliability")?;
+
+        let attempts: Vec<_> = attempts
+            .iter()
+            .filter_map(|attempt| {
+                let generated_exam = generated_exams
+                    .iter()
+                    .find(|ge| ge.id == attempt.generated_exam_id)?;
+                Some(construct_attempt(exam, generated_exam, attempt))
+            })
+            .collect();
+
+        let analysis = analyse_items(exam.id, &attempts);
+        tracing::debug!(
+            exam = %exam.id,
+            num_attempts = analysis.num_attempts,
+            num_questions = analysis.questions.len(),
+            "item analysis"
+        );
+        analysis_collection
+            .replace_one(doc! {"examId": exam.id}, &analysis)
+            .upsert(true)
+            .await
+            .context("unable to upsert item analysis")?;
+    }
+
+    tracing::info!(num_exams = exams.len(), "updated item analyses");
+
+    Ok(())
+}
+
+/// Reports attempts of the same exam, started within `collusion_window_in_s`, with suspiciously similar answers and timelines.
+/// Each cluster of attempts is upserted into the `ExamEnvironmentCollusionReport` collection for moderators to review.
+#[tracing::instrument(skip_all, err(Debug))]
+pub async fn detect_collusion(env_vars: &EnvVars) -> anyhow::Result<()> {
+    let client = client(&env_vars.mongodb_uri).await?;
+
+    let attempt_collection =
+        get_collection::<ExamEnvironmentExamAttempt>(&client, "ExamEnvironmentExamAttempt").await;
+    let exam_collection =
+        get_collection::<ExamEnvironmentExam>(&client, "ExamEnvironmentExam").await;
+    let generated_exam_collection =
+        get_collection::<ExamEnvironmentGeneratedExam>(&client, "ExamEnvironmentGeneratedExam")
+            .await;
+    let report_collection =
+        get_collection::<CollusionReport>(&client, "ExamEnvironmentCollusionReport").await;
+
+    let now = DateTime::now();
+    let window_start = DateTime::from_millis(
+        now.timestamp_millis() - env_vars.collusion_window_in_s.as_millis() as i64,
+    );
+    let practice_exam_id =
+        ObjectId::parse_str(PRACTICE_EXAM_ID).expect("static str is valid object id");
+
+    let attempts: Vec<ExamEnvironmentExamAttempt> = attempt_collection
+        .find(doc! {
+            "examId": {"$ne": practice_exam_id},
+            "startTime": {"$gte": window_start}
+        })
+        .await
+        .context("unable to find attempts in collusion window")?
+        .try_collect()
+        .await
+        .context("unable to deserialize attempts")?;
+
+    let unique_exam_ids = attempts
+        .iter()
+        .map(|a| a.exam_id)
+        .collect::<std::collections::HashSet<_>>();
+    let unique_generated_exam_ids = attempts
+        .iter()
+        .map(|a| a.generated_exam_id)
+        .collect::<std::collections::HashSet<_>>();
+
+    let exams = exam_collection
+        .find(doc! {"_id": {"$in": unique_exam_ids}})
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    let generated_exams = generated_exam_collection
+        .find(doc! {"_id": {"$in": unique_generated_exam_ids}})
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut constructed_attempts = vec![];
+    for attempt in attempts.iter() {
+        let Some(exam) = exams.iter().find(|e| e.id == attempt.exam_id) else {
+            tracing::warn!(attempt = %attempt.id, "unable to find exam for attempt");
+            continue;
+        };
+        let Some(generated_exam) = generated_exams
+            .iter()
+            .find(|ge| ge.id == attempt.generated_exam_id)
+        else {
+            tracing::warn!(attempt = %attempt.id, "unable to find generated exam for attempt");
+            continue;
+        };
+        constructed_attempts.push(construct_attempt(exam, generated_exam, attempt));
+    }
+
+    let clusters = detect_collusion_clusters(&constructed_attempts, &CollusionConfig::default());
+
+    for cluster in clusters.iter() {
+        tracing::info!(
+            exam = %cluster.exam_id,
+            attempts = ?cluster.attempt_ids,
+            "suspected collusion"
+        );
+        let pairs = mongodb::bson::serialize_to_bson(&cluster.pairs)?;
+        report_collection
+            .update_one(
+                doc! {"examId": cluster.exam_id, "attemptIds": &cluster.attempt_ids},
+                doc! {
+                    "$set": {"pairs": pairs, "updatedAt": now},
+                    "$setOnInsert": {"createdAt": now}
+                },
+            )
+            .upsert(true)
+            .await
+            .context("unable to upsert collusion report")?;
+    }
+
+    tracing::info!(
+        num_attempts = constructed_attempts.len(),
+        num_clusters = clusters.len(),
+    );
+
+    Ok(())
+}
+
+/// Record in the `ExamEnvironmentCollusionReport` collection
+#[derive(Deserialize, Serialize)]
+struct CollusionReport {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    #[serde(rename = "examId")]
+    exam_id: ObjectId,
+    /// Sorted ids of the attempts in the cluster
+    #[serde(rename = "attemptIds")]
+    attempt_ids: Vec<ObjectId>,
+    pairs: Vec<exam_utils::collusion::AttemptComparison>,
+    #[serde(rename = "createdAt")]
+    created_at: DateTime,
+    #[serde(rename = "updatedAt")]
+    updated_at: DateTime,
+}
+
 #[derive(Deserialize, Serialize)]
 struct User {
     #[serde(rename = "_id")]
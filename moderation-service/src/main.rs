@@ -6,7 +6,8 @@ use moderation_service::{
     config::EnvVars,
     db::{
         auto_approve_moderation_records, award_challenge_ids, delete_practice_exam_attempts,
-        delete_supabase_events, update_moderation_collection,
+        delete_supabase_events, detect_collusion, update_item_analyses,
+        update_moderation_collection, update_timing_baselines,
     },
 };
 use tracing::{error, info};
@@ -119,6 +120,14 @@ async fn run_registered_tasks(env_vars: &EnvVars) {
                 Box::pin(async move { delete_practice_exam_attempts(&env).await }),
             )
         },
+        {
+            // Update timing baselines before they are used to score new moderations
+            let env = env_vars.clone();
+            (
+                "update_timing_baselines",
+                Box::pin(async move { update_timing_baselines(&env).await }),
+            )
+        },
         {
             // Update the moderation collection to represent current state of attempts before tasks altering moderations
             let env = env_vars.clone();
@@ -127,6 +136,22 @@ async fn run_registered_tasks(env_vars: &EnvVars) {
                 Box::pin(async move { update_moderation_collection(&env).await }),
             )
         },
+        {
+            // Report attempts of the same exam with suspiciously similar answers and timelines
+            let env = env_vars.clone();
+            (
+                "detect_collusion",
+                Box::pin(async move { detect_collusion(&env).await }),
+            )
+        },
+        {
+            // Refresh question and answer statistics used to retire bad items
+            let env = env_vars.clone();
+            (
+                "update_item_analyses",
+                Box::pin(async move { update_item_analyses(&env).await }),
+            )
+        },
         {
             // Approve old-enough moderations
             let env = env_vars.clone();
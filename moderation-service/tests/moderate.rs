@@ -1,7 +1,10 @@
 // #![allow(incomplete_features)]
 // #![feature(async_drop)]
 use futures_util::TryStreamExt;
-use moderation_service::{config::EnvVars, db::update_moderation_collection};
+use moderation_service::{
+    config::EnvVars,
+    db::{auto_approve_moderation_records, update_moderation_collection},
+};
 // use mongo_drop::MongoDrop;
 use mongodb::bson::{doc, oid::ObjectId};
 use prisma::*;
@@ -206,3 +209,77 @@ async fn moderation_record_is_created() {
     assert_eq!(record_2.feedback, Some("Auto Approved".to_string()));
     assert_eq!(record_3.feedback, Some("Auto Approved".to_string()));
 }
+
+/// Add an expired record flagged with integrity issues, and an expired unflagged record
+/// Call auto approve, ensure only the unflagged record is approved, and the flagged feedback is kept
+#[tokio::test]
+#[tracing_test::traced_test]
+async fn flagged_moderation_record_is_not_auto_approved() {
+    dotenvy::dotenv().ok();
+    let mongo_uri = std::env::var("MONGODB_URI").unwrap();
+    let client = db::client(&mongo_uri).await.unwrap();
+
+    let moderation_collection = db::get_collection::<prisma::ExamEnvironmentExamModeration>(
+        &client,
+        "ExamEnvironmentExamModeration",
+    )
+    .await;
+
+    let submission_date = mongodb::bson::DateTime::now();
+    let flagged_feedback =
+        "Integrity issues: submission before attempt start. Moderation score: 0.01".to_string();
+    let flagged_record = prisma::ExamEnvironmentExamModeration {
+        id: ObjectId::new(),
+        exam_attempt_id: ObjectId::new(),
+        status: prisma::ExamEnvironmentExamModerationStatus::Pending,
+        feedback: Some(flagged_feedback.clone()),
+        submission_date,
+        ..Default::default()
+    };
+    let unflagged_record = prisma::ExamEnvironmentExamModeration {
+        id: ObjectId::new(),
+        exam_attempt_id: ObjectId::new(),
+        status: prisma::ExamEnvironmentExamModerationStatus::Pending,
+        feedback: Some("Moderation score: 0.5".to_string()),
+        submission_date,
+        ..Default::default()
+    };
+    moderation_collection
+        .insert_many([&flagged_record, &unflagged_record])
+        .await
+        .unwrap();
+
+    let mut env_vars = EnvVars::new();
+    env_vars.moderation_length_in_s = std::time::Duration::from_secs(1);
+
+    // Ensure at least 1 second has passed
+    tokio::time::sleep(std::time::Duration::from_millis(1_500)).await;
+
+    auto_approve_moderation_records(&env_vars).await.unwrap();
+
+    let flagged_record = moderation_collection
+        .find_one(doc! {"_id": flagged_record.id})
+        .await
+        .unwrap()
+        .unwrap();
+    let unflagged_record = moderation_collection
+        .find_one(doc! {"_id": unflagged_record.id})
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        flagged_record.status,
+        prisma::ExamEnvironmentExamModerationStatus::Pending
+    );
+    assert_eq!(flagged_record.feedback, Some(flagged_feedback));
+    assert_eq!(flagged_record.moderation_date, None);
+    assert_eq!(
+        unflagged_record.status,
+        prisma::ExamEnvironmentExamModerationStatus::Approved
+    );
+    assert_eq!(
+        unflagged_record.feedback,
+        Some("Auto Approved - Moderation time exceeded".to_string())
+    );
+}
@@ -4,6 +4,9 @@ pub async fn _validate_all_generations(client: Client) {
         "ExamEnvironmentGeneratedExam",
     )
     .await;
+    let exam_collection =
+        get_collection::<prisma::ExamEnvironmentExam>(&client, "ExamEnvironmentExam").await;
+    let mut exams = HashMap::new();
 
     let generation_count = generation_collection
         .count_documents(doc! {})
@@ -21,7 +24,19 @@ pub async fn _validate_all_generations(client: Client) {
 
     while let Some(generation) = generation_cursor.next().await {
         let generation = generation.expect("unable to deserialize generation");
-        match exam_utils::generation::validate_generation(&generation) {
+        let Some(exam) = get_from_cache_or_collection(
+            &exam_collection,
+            doc! {"_id": generation.exam_id},
+            &mut exams,
+            generation.exam_id,
+        )
+        .await
+        else {
+            println!("Exam not found for Generation: {}", generation.id);
+            pb.inc(1);
+            continue;
+        };
+        match exam_utils::generation::validate_generation_against_exam(&exam, &generation) {
             Ok(_) => {}
             Err(e) => {
                 println!("Invalid Generation: {}", generation.id);